@@ -0,0 +1,203 @@
+use serde_derive::Serialize;
+use std::{
+    collections::VecDeque,
+    io::{Read, Write},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+use tiny_http::{Header, Request, Response, StatusCode};
+use tungstenite::{handshake::derive_accept_key, protocol::Role, Message, WebSocket};
+
+use crate::state::{StateEvent, StateRecord};
+
+/// Number of events replayed to a client right after it connects.
+static REPLAY_LIMIT: usize = 50;
+/// Idle connections are pinged so closed clients are noticed and dropped.
+static PING_INTERVAL: Duration = Duration::from_secs(30);
+/// How long a read of the client's frames waits, applied to the API sockets by `start_api_server`.
+pub static READ_TIMEOUT: Duration = Duration::from_millis(500);
+/// On Windows the wait happens in `read_client_frame`, elsewhere the sockets have no timeout.
+#[cfg(windows)]
+static EVENT_WAIT: Duration = Duration::ZERO;
+#[cfg(not(windows))]
+static EVENT_WAIT: Duration = PING_INTERVAL;
+
+#[derive(Serialize)]
+struct EventMessage<'a> {
+    /// Unix timestamp in milliseconds.
+    at: u64,
+    #[serde(flatten)]
+    event: &'a StateEvent,
+}
+
+fn encode_record(record: &StateRecord) -> String {
+    let at = record
+        .at
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_millis() as u64)
+        .unwrap_or(0);
+
+    serde_json::to_string(&EventMessage {
+        at,
+        event: &record.event,
+    })
+    .unwrap_or_default()
+}
+
+/// Fans state changes out to every connected WebSocket client.
+pub struct EventHub {
+    clients: Mutex<Vec<mpsc::Sender<String>>>,
+    replay: Mutex<VecDeque<String>>,
+}
+
+impl EventHub {
+    pub fn new<'a>(history: impl Iterator<Item = &'a StateRecord>) -> Arc<Self> {
+        let mut replay: VecDeque<String> = history.map(encode_record).collect();
+        while replay.len() > REPLAY_LIMIT {
+            replay.pop_front();
+        }
+
+        Arc::new(EventHub {
+            clients: Mutex::new(Vec::new()),
+            replay: Mutex::new(replay),
+        })
+    }
+
+    fn publish(&self, record: &StateRecord) {
+        let message = encode_record(record);
+
+        let (Ok(mut clients), Ok(mut replay)) = (self.clients.lock(), self.replay.lock()) else {
+            return;
+        };
+
+        clients.retain(|client| client.send(message.clone()).is_ok());
+
+        if replay.len() == REPLAY_LIMIT {
+            replay.pop_front();
+        }
+        replay.push_back(message);
+    }
+
+    /// Registers a client and returns the events it should replay first.
+    fn connect(&self) -> (Vec<String>, mpsc::Receiver<String>) {
+        let (sender, receiver) = mpsc::channel();
+
+        /* Both locks are held, like in `publish`, so no event is missed or sent twice */
+        let mut clients = self.clients.lock().unwrap();
+        let replay = self.replay.lock().unwrap();
+
+        clients.push(sender);
+
+        (replay.iter().cloned().collect(), receiver)
+    }
+}
+
+/// Forwards the state machine's changes to the hub, see `AppState::subscribe`.
+pub fn event_hub_thread(hub: Arc<EventHub>, records: mpsc::Receiver<StateRecord>) {
+    for record in records {
+        hub.publish(&record);
+    }
+}
+
+fn find_header<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.as_str())
+}
+
+/// Reads the client's next frame and answers a Close, false once the client is gone.
+#[cfg(windows)]
+fn read_client_frame<S: Read + Write>(websocket: &mut WebSocket<S>) -> bool {
+    match websocket.read() {
+        Ok(Message::Close(_)) => {
+            /* The reply to the Close is queued by tungstenite */
+            websocket.flush().ok();
+            false
+        }
+        Ok(_) => true,
+        Err(tungstenite::Error::Io(e)) => matches!(
+            e.kind(),
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+        ),
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(windows))]
+fn read_client_frame<S: Read + Write>(_websocket: &mut WebSocket<S>) -> bool {
+    true
+}
+
+fn stream_events<S: Read + Write>(
+    mut websocket: WebSocket<S>,
+    replay: Vec<String>,
+    events: mpsc::Receiver<String>,
+) {
+    for message in replay {
+        if websocket.send(Message::Text(message)).is_err() {
+            return;
+        }
+    }
+
+    let mut last_sent_at = Instant::now();
+
+    loop {
+        let message = match events.recv_timeout(EVENT_WAIT) {
+            Ok(event) => Message::Text(event),
+            Err(mpsc::RecvTimeoutError::Timeout) if last_sent_at.elapsed() >= PING_INTERVAL => {
+                Message::Ping(Vec::new())
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                if !read_client_frame(&mut websocket) {
+                    return;
+                }
+                continue;
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+
+        if websocket.send(message).is_err() {
+            break;
+        }
+
+        last_sent_at = Instant::now();
+    }
+
+    websocket.close(None).ok();
+    websocket.flush().ok();
+}
+
+/// Completes the WebSocket handshake and streams events on a dedicated thread.
+pub fn serve_event_stream(request: Request, hub: Arc<EventHub>) {
+    let is_upgrade = find_header(&request, "Upgrade")
+        .map(|upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+        .unwrap_or(false);
+    let key = find_header(&request, "Sec-WebSocket-Key").map(str::to_string);
+
+    let Some(key) = key.filter(|_| is_upgrade) else {
+        request
+            .respond(Response::from_string("WebSocket upgrade expected").with_status_code(400))
+            .ok();
+        return;
+    };
+
+    let response = Response::empty(StatusCode(101))
+        .with_header(Header::from_bytes("Upgrade", "websocket").unwrap())
+        .with_header(Header::from_bytes("Connection", "Upgrade").unwrap())
+        .with_header(
+            Header::from_bytes("Sec-WebSocket-Accept", derive_accept_key(key.as_bytes())).unwrap(),
+        );
+
+    thread::spawn(move || {
+        let stream = request.upgrade("websocket", response);
+        let websocket = WebSocket::from_raw_socket(stream, Role::Server, None);
+        let (replay, events) = hub.connect();
+
+        log::debug!("Event stream client connected");
+        stream_events(websocket, replay, events);
+        log::debug!("Event stream client disconnected");
+    });
+}
@@ -0,0 +1,460 @@
+use serde::Serialize;
+use std::{
+    io::Cursor,
+    net::{IpAddr, SocketAddr, TcpListener},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+
+use super::events::{serve_event_stream, EventHub, READ_TIMEOUT};
+use crate::{
+    settings::ApiSettings,
+    state::StatusSnapshot,
+    utils::{clients::ConnectedClient, simconnect::read_simconnect_config, udp::BroadcastStats},
+};
+
+/// Everything the request handlers read or trigger, shared with the UI thread.
+#[derive(Clone)]
+pub struct ApiContext {
+    pub status: Arc<Mutex<StatusSnapshot>>,
+    pub broadcast_stats: Arc<Mutex<BroadcastStats>>,
+    /// Asks the UI to restart the broadcaster, false once it is shutting down.
+    pub restart_broadcast: Arc<dyn Fn() -> bool + Send + Sync>,
+    pub events: Arc<EventHub>,
+    pub clients: Arc<Mutex<Vec<ConnectedClient>>>,
+}
+
+#[derive(Serialize)]
+struct StatusResponse {
+    #[serde(flatten)]
+    status: StatusSnapshot,
+    broadcast_stats: BroadcastStats,
+    clients: Vec<ConnectedClient>,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse<'a> {
+    error: &'a str,
+}
+
+type JsonResponse = Response<Cursor<Vec<u8>>>;
+
+fn json_response<T: Serialize>(status_code: u16, body: &T) -> JsonResponse {
+    let body = serde_json::to_vec(body).unwrap_or_default();
+
+    Response::from_data(body)
+        .with_status_code(StatusCode(status_code))
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap())
+}
+
+fn error_response(status_code: u16, error: &str) -> JsonResponse {
+    json_response(status_code, &ErrorResponse { error })
+}
+
+/// Compares without returning early so the token can't be guessed from response times.
+fn is_token_equal(expected: &str, actual: &str) -> bool {
+    expected.len() == actual.len()
+        && expected
+            .bytes()
+            .zip(actual.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+fn request_path(request: &Request) -> &str {
+    request.url().split('?').next().unwrap_or_default()
+}
+
+/// Browsers can't set headers on WebSocket connections, so `/events` also takes `?token=`.
+fn query_token(request: &Request) -> Option<&str> {
+    if request_path(request) != "/events" {
+        return None;
+    }
+
+    request
+        .url()
+        .split_once('?')?
+        .1
+        .split('&')
+        .find_map(|parameter| parameter.strip_prefix("token="))
+}
+
+fn is_authorized(request: &Request, token: &Option<String>) -> bool {
+    let Some(token) = token else {
+        return true;
+    };
+
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Authorization"))
+        .and_then(|header| header.value.as_str().strip_prefix("Bearer "))
+        .map(str::trim)
+        .or_else(|| query_token(request))
+        .map(|actual| is_token_equal(token, actual))
+        .unwrap_or(false)
+}
+
+fn handle_request(request: &Request, context: &ApiContext) -> JsonResponse {
+    let path = request_path(request);
+
+    match (request.method(), path) {
+        (Method::Get, "/status") => {
+            let status = context.status.lock().map(|status| status.clone());
+            let broadcast_stats = context.broadcast_stats.lock().map(|stats| stats.clone());
+            let clients = context.clients.lock().map(|clients| clients.clone());
+
+            match (status, broadcast_stats, clients) {
+                (Ok(status), Ok(broadcast_stats), Ok(clients)) => json_response(
+                    200,
+                    &StatusResponse {
+                        status,
+                        broadcast_stats,
+                        clients,
+                    },
+                ),
+                _ => error_response(500, "State is unavailable"),
+            }
+        }
+        (Method::Get, "/clients") => match context.clients.lock() {
+            Ok(clients) => json_response(200, &*clients),
+            Err(_) => error_response(500, "State is unavailable"),
+        },
+        (Method::Get, "/config") => match read_simconnect_config() {
+            Ok(config) => json_response(200, &config),
+            Err(error) => error_response(500, &error),
+        },
+        (Method::Post, "/broadcast/restart") => {
+            if (context.restart_broadcast)() {
+                json_response(202, &serde_json::json!({ "restarting": true }))
+            } else {
+                error_response(503, "Client is shutting down")
+            }
+        }
+        (_, "/status")
+        | (_, "/clients")
+        | (_, "/config")
+        | (_, "/broadcast/restart")
+        | (_, "/events") => error_response(405, "Method not allowed"),
+        _ => error_response(404, "Not found"),
+    }
+}
+
+fn api_server_thread(server: Server, token: Option<String>, context: ApiContext) {
+    for request in server.incoming_requests() {
+        let is_authorized = is_authorized(&request, &token);
+
+        /* The upgrade takes over the connection, so the stream is served outside `handle_request` */
+        if is_authorized && request.method() == &Method::Get && request_path(&request) == "/events"
+        {
+            serve_event_stream(request, context.events.clone());
+            continue;
+        }
+
+        let response = if is_authorized {
+            handle_request(&request, &context)
+        } else {
+            error_response(401, "Unauthorized")
+        };
+
+        if let Err(e) = request.respond(response) {
+            log::debug!("Error sending API response: {}", e);
+        }
+    }
+}
+
+/// Accepted sockets inherit the listener's receive timeout, so the event stream can read the
+/// client's frames between events. Idle HTTP connections are closed after it as well.
+#[cfg(windows)]
+fn set_accepted_read_timeout(listener: &TcpListener, timeout: Duration) -> Result<(), String> {
+    use std::{mem::size_of, os::windows::io::AsRawSocket};
+    use windows_sys::Win32::Networking::WinSock::{setsockopt, SOL_SOCKET, SO_RCVTIMEO};
+
+    let timeout = timeout.as_millis() as u32;
+
+    let result = unsafe {
+        setsockopt(
+            listener.as_raw_socket() as _,
+            SOL_SOCKET,
+            SO_RCVTIMEO,
+            &timeout as *const u32 as *const u8,
+            size_of::<u32>() as i32,
+        )
+    };
+
+    if result != 0 {
+        return Err(format!(
+            "Error setting the API read timeout: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+
+    Ok(())
+}
+
+/* Elsewhere `accept` times out as well, which would stop the server */
+#[cfg(not(windows))]
+fn set_accepted_read_timeout(_listener: &TcpListener, _timeout: Duration) -> Result<(), String> {
+    Ok(())
+}
+
+/// Starts the API on its own thread and returns the address it listens on.
+/// Exposing it beyond loopback requires a token.
+pub fn start_api_server(settings: &ApiSettings, context: ApiContext) -> Result<SocketAddr, String> {
+    let bind_address: IpAddr = settings
+        .bind
+        .parse()
+        .map_err(|e| format!("Invalid API bind address '{}': {}", settings.bind, e))?;
+
+    let token = settings.token.clone().filter(|token| !token.is_empty());
+
+    if !bind_address.is_loopback() && token.is_none() {
+        return Err("API token is required when the API is not bound to loopback.".to_string());
+    }
+
+    let listener = TcpListener::bind((bind_address, settings.port))
+        .map_err(|e| format!("Error starting API server: {}", e))?;
+
+    set_accepted_read_timeout(&listener, READ_TIMEOUT)?;
+
+    let server = Server::from_listener(listener, None)
+        .map_err(|e| format!("Error starting API server: {}", e))?;
+
+    let address = server
+        .server_addr()
+        .to_ip()
+        .ok_or("API server is not listening on an IP address")?;
+
+    log::info!("API listening on {}", address);
+
+    thread::spawn(move || api_server_thread(server, token, context));
+
+    Ok(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::events::event_hub_thread,
+        state::{AppState, StateEvent},
+    };
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+    use tungstenite::{client::IntoClientRequest, Message, WebSocket};
+
+    fn start_server(
+        bind: &str,
+        token: Option<&str>,
+    ) -> Result<(SocketAddr, Arc<AtomicBool>), String> {
+        start_server_with_events(bind, token, EventHub::new(std::iter::empty()))
+    }
+
+    fn start_server_with_events(
+        bind: &str,
+        token: Option<&str>,
+        events: Arc<EventHub>,
+    ) -> Result<(SocketAddr, Arc<AtomicBool>), String> {
+        let is_restart_requested = Arc::new(AtomicBool::new(false));
+        let restart_flag = is_restart_requested.clone();
+
+        let context = ApiContext {
+            status: Arc::new(Mutex::new(AppState::new().snapshot())),
+            broadcast_stats: Arc::new(Mutex::new(BroadcastStats::default())),
+            restart_broadcast: Arc::new(move || {
+                restart_flag.store(true, Ordering::SeqCst);
+                true
+            }),
+            events,
+            clients: Arc::new(Mutex::new(Vec::new())),
+        };
+
+        let settings = ApiSettings {
+            enabled: true,
+            bind: bind.to_string(),
+            port: 0,
+            token: token.map(str::to_string),
+        };
+
+        start_api_server(&settings, context).map(|address| (address, is_restart_requested))
+    }
+
+    /// Sends a bare HTTP/1.0 request, returns the status code and the body.
+    fn request(
+        address: SocketAddr,
+        method: &str,
+        path: &str,
+        token: Option<&str>,
+    ) -> (u16, String) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        let authorization = token
+            .map(|token| format!("Authorization: Bearer {}\r\n", token))
+            .unwrap_or_default();
+
+        write!(
+            stream,
+            "{} {} HTTP/1.0\r\nHost: localhost\r\n{}Content-Length: 0\r\n\r\n",
+            method, path, authorization
+        )
+        .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status_code = head
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .unwrap();
+
+        (status_code, body.to_string())
+    }
+
+    /// Opens the event stream, with the token in the `Authorization` header if there is one.
+    fn connect_events(
+        address: SocketAddr,
+        path: &str,
+        token: Option<&str>,
+    ) -> Result<WebSocket<TcpStream>, String> {
+        let mut request = format!("ws://{}{}", address, path)
+            .into_client_request()
+            .unwrap();
+
+        if let Some(token) = token {
+            request.headers_mut().insert(
+                "Authorization",
+                format!("Bearer {}", token).parse().unwrap(),
+            );
+        }
+
+        let stream = TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        tungstenite::client(request, stream)
+            .map(|(websocket, _)| websocket)
+            .map_err(|e| e.to_string())
+    }
+
+    fn read_event(websocket: &mut WebSocket<TcpStream>) -> serde_json::Value {
+        loop {
+            match websocket.read().unwrap() {
+                Message::Text(text) => return serde_json::from_str(&text).unwrap(),
+                Message::Ping(_) | Message::Pong(_) => continue,
+                message => panic!("Unexpected message {:?}", message),
+            }
+        }
+    }
+
+    #[test]
+    fn status_is_served_as_json_on_loopback() {
+        let (address, _) = start_server("127.0.0.1", None).unwrap();
+
+        let (status_code, body) = request(address, "GET", "/status", None);
+        let status: serde_json::Value = serde_json::from_str(&body).unwrap();
+
+        assert_eq!(status_code, 200);
+        assert_eq!(status["status"], "neutral");
+        assert_eq!(status["broadcast_stats"]["packets_sent"], 0);
+        assert!(status["clients"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn unknown_paths_and_methods_are_rejected() {
+        let (address, _) = start_server("127.0.0.1", None).unwrap();
+
+        assert_eq!(request(address, "GET", "/nothing", None).0, 404);
+        assert_eq!(request(address, "POST", "/status", None).0, 405);
+    }
+
+    #[test]
+    fn exposing_beyond_loopback_requires_a_token() {
+        assert!(start_server("0.0.0.0", None).is_err());
+        assert!(start_server("0.0.0.0", Some("")).is_err());
+    }
+
+    #[test]
+    fn token_is_required_when_set() {
+        let (address, is_restart_requested) = start_server("0.0.0.0", Some("secret")).unwrap();
+        let address = SocketAddr::from(([127, 0, 0, 1], address.port()));
+
+        assert_eq!(request(address, "GET", "/status", None).0, 401);
+        assert_eq!(request(address, "GET", "/status", Some("wrong")).0, 401);
+        assert_eq!(request(address, "POST", "/broadcast/restart", None).0, 401);
+        assert!(!is_restart_requested.load(Ordering::SeqCst));
+
+        assert_eq!(request(address, "GET", "/status", Some("secret")).0, 200);
+        assert_eq!(
+            request(address, "POST", "/broadcast/restart", Some("secret")).0,
+            202
+        );
+        assert!(is_restart_requested.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn event_stream_takes_the_token_from_the_query() {
+        let (address, _) = start_server("127.0.0.1", Some("secret")).unwrap();
+
+        assert_eq!(request(address, "GET", "/events?token=wrong", None).0, 401);
+        assert_eq!(request(address, "GET", "/status?token=secret", None).0, 401);
+    }
+
+    #[test]
+    fn event_stream_replays_history_before_live_events() {
+        let mut app_state = AppState::new();
+        app_state.apply(StateEvent::AnnouncementsPaused(true));
+
+        let events = EventHub::new(app_state.history());
+        let state_records = app_state.subscribe();
+        let hub = events.clone();
+        thread::spawn(move || event_hub_thread(hub, state_records));
+
+        let (address, _) = start_server_with_events("127.0.0.1", Some("secret"), events).unwrap();
+
+        assert!(connect_events(address, "/events", None).is_err());
+        assert!(connect_events(address, "/events?token=wrong", None).is_err());
+
+        let mut websocket = connect_events(address, "/events", Some("secret")).unwrap();
+
+        let replayed = read_event(&mut websocket);
+        assert_eq!(replayed["type"], "announcements_paused");
+        assert_eq!(replayed["data"], true);
+
+        app_state.apply(StateEvent::AnnouncementsPaused(false));
+
+        let live = read_event(&mut websocket);
+        assert_eq!(live["type"], "announcements_paused");
+        assert_eq!(live["data"], false);
+
+        let mut websocket = connect_events(address, "/events?token=secret", None).unwrap();
+
+        assert_eq!(read_event(&mut websocket)["data"], true);
+        assert_eq!(read_event(&mut websocket)["data"], false);
+    }
+
+    /* The stream only reads the client's frames where the sockets have a read timeout */
+    #[cfg(windows)]
+    #[test]
+    fn event_stream_answers_a_close() {
+        let (address, _) = start_server("127.0.0.1", None).unwrap();
+        let mut websocket = connect_events(address, "/events", None).unwrap();
+
+        websocket.close(None).unwrap();
+
+        let error = loop {
+            match websocket.read() {
+                Ok(_) => continue,
+                Err(e) => break e,
+            }
+        };
+
+        assert!(matches!(error, tungstenite::Error::ConnectionClosed));
+    }
+}
@@ -0,0 +1,38 @@
+use std::{net::IpAddr, thread};
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::{settings::MetricsSettings, utils::metrics::metrics};
+
+fn metrics_server_thread(server: Server) {
+    for request in server.incoming_requests() {
+        let path = request.url().split('?').next().unwrap_or_default();
+
+        let response = match (request.method(), path) {
+            (Method::Get, "/metrics") => Response::from_string(metrics().render()).with_header(
+                Header::from_bytes("Content-Type", "text/plain; version=0.0.4").unwrap(),
+            ),
+            _ => Response::from_string("Not found").with_status_code(404),
+        };
+
+        if let Err(e) = request.respond(response) {
+            log::debug!("Error sending metrics response: {}", e);
+        }
+    }
+}
+
+/// Serves `GET /metrics` in the Prometheus text format on its own thread.
+pub fn start_metrics_server(settings: &MetricsSettings) -> Result<(), String> {
+    let bind_address: IpAddr = settings
+        .bind
+        .parse()
+        .map_err(|e| format!("Invalid metrics bind address '{}': {}", settings.bind, e))?;
+
+    let server = Server::http((bind_address, settings.port))
+        .map_err(|e| format!("Error starting metrics server: {}", e))?;
+
+    log::info!("Metrics listening on {}:{}", bind_address, settings.port);
+
+    thread::spawn(move || metrics_server_thread(server));
+
+    Ok(())
+}
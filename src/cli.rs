@@ -0,0 +1,157 @@
+use std::path::PathBuf;
+
+use crate::APP_TITLE;
+
+pub enum Command {
+    /// Default mode: the status window, tray icon and broadcaster.
+    Run,
+    /// Writes the diagnostics bundle, to the given path or the desktop.
+    Diagnostics {
+        output: Option<PathBuf>,
+    },
+    /// Forwards announcements between the machine's networks, without the window.
+    Reflector,
+    /// Shows the autostart registration, or turns it on or off.
+    Autostart {
+        enabled: Option<bool>,
+    },
+    Help,
+}
+
+pub fn usage() -> String {
+    format!(
+        "Usage: {} [COMMAND]\n\n\
+         Commands:\n  \
+         diagnostics [OUTPUT.zip]  Export a diagnostics bundle for support tickets\n  \
+         reflector                 Re-announce packets from one network on the others\n  \
+         autostart [on|off]        Show or change whether the client starts at login\n  \
+         help                      Show this message\n\n\
+         Without a command the client starts normally.",
+        APP_TITLE
+    )
+}
+
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let command = match args.next() {
+        None => Command::Run,
+        Some(command) => match command.as_str() {
+            "diagnostics" => Command::Diagnostics {
+                output: args.next().map(PathBuf::from),
+            },
+            "reflector" => Command::Reflector,
+            "autostart" => Command::Autostart {
+                enabled: match args.next().as_deref() {
+                    None => None,
+                    Some("on") => Some(true),
+                    Some("off") => Some(false),
+                    Some(other) => return Err(format!("Expected 'on' or 'off', got '{}'.", other)),
+                },
+            },
+            "help" | "--help" | "-h" => Command::Help,
+            unknown => return Err(format!("Unknown command '{}'.", unknown)),
+        },
+    };
+
+    match args.next() {
+        Some(extra) => Err(format!("Unexpected argument '{}'.", extra)),
+        None => Ok(command),
+    }
+}
+
+/// Release builds have no console of their own, so CLI output goes to the launching one.
+#[cfg(windows)]
+pub fn attach_parent_console() {
+    use windows_sys::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+pub fn attach_parent_console() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn no_arguments_runs_the_client() {
+        assert!(matches!(parse(&[]), Ok(Command::Run)));
+    }
+
+    #[test]
+    fn diagnostics_with_and_without_output() {
+        assert!(matches!(
+            parse(&["diagnostics"]),
+            Ok(Command::Diagnostics { output: None })
+        ));
+        assert!(matches!(
+            parse(&["diagnostics", "bundle.zip"]),
+            Ok(Command::Diagnostics { output: Some(output) }) if output == Path::new("bundle.zip")
+        ));
+    }
+
+    #[test]
+    fn help_flags() {
+        for flag in ["help", "--help", "-h"] {
+            assert!(matches!(parse(&[flag]), Ok(Command::Help)), "{}", flag);
+        }
+    }
+
+    #[test]
+    fn unknown_and_extra_arguments_are_rejected() {
+        assert_eq!(
+            parse(&["--verbose"]).err(),
+            Some("Unknown command '--verbose'.".to_string())
+        );
+        assert_eq!(
+            parse(&["diagnostics", "a.zip", "b.zip"]).err(),
+            Some("Unexpected argument 'b.zip'.".to_string())
+        );
+        assert!(parse(&["help", "diagnostics"]).is_err());
+    }
+
+    #[test]
+    fn reflector() {
+        assert!(matches!(parse(&["reflector"]), Ok(Command::Reflector)));
+        assert_eq!(
+            parse(&["reflector", "now"]).err(),
+            Some("Unexpected argument 'now'.".to_string())
+        );
+    }
+
+    #[test]
+    fn autostart_state() {
+        assert!(matches!(
+            parse(&["autostart"]),
+            Ok(Command::Autostart { enabled: None })
+        ));
+        assert!(matches!(
+            parse(&["autostart", "on"]),
+            Ok(Command::Autostart {
+                enabled: Some(true)
+            })
+        ));
+        assert!(matches!(
+            parse(&["autostart", "off"]),
+            Ok(Command::Autostart {
+                enabled: Some(false)
+            })
+        ));
+    }
+
+    #[test]
+    fn invalid_autostart_state_is_rejected() {
+        assert_eq!(
+            parse(&["autostart", "yes"]).err(),
+            Some("Expected 'on' or 'off', got 'yes'.".to_string())
+        );
+        assert!(parse(&["autostart", "on", "off"]).is_err());
+    }
+}
@@ -3,69 +3,188 @@
 
 include!("./env.rs");
 
+mod api;
+mod cli;
 mod schema;
+mod settings;
 mod state;
 mod ui;
 mod utils;
 
+use api::{
+    events::{event_hub_thread, EventHub},
+    http::{start_api_server, ApiContext},
+    metrics::start_metrics_server,
+};
 use ui::{
+    controls::Controls,
     icons::get_window_icon,
-    system_try::{SystemTry, MENU_ITEM_EXIT_ID, MENU_ITEM_STATUS_ID},
+    layout::{Layout, Offset, Slot},
+    notification::notification_thread,
+    renderer::create_renderer,
+    system_try::{
+        SystemTry, TryMenuState, MENU_ITEM_AUTOSTART_ID, MENU_ITEM_COPY_CONNECTION_ID,
+        MENU_ITEM_DIAGNOSTICS_ID, MENU_ITEM_EXIT_ID, MENU_ITEM_LOG_ID,
+        MENU_ITEM_OPEN_CONFIG_FOLDER_ID, MENU_ITEM_OPEN_SETTINGS_ID, MENU_ITEM_PAUSE_ID,
+        MENU_ITEM_REAPPLY_CONFIG_ID, MENU_ITEM_RESTART_BROADCAST_ID, MENU_ITEM_RESTORE_CONFIG_ID,
+        MENU_ITEM_STATUS_ID,
+    },
 };
 use utils::{
-    msfs::check_if_msfs_running,
-    simconnect::update_simconnect_config,
-    udp::{udp_broadcast_thread, UDP_THREAD_STATUS_ERROR, UDP_THREAD_STATUS_OK},
-    wgpu::configure_wgpu,
+    autostart::{is_autostart_enabled, set_autostart},
+    clients::{client_watch_thread, describe_clients, ConnectedClient},
+    diagnostics::{export_diagnostics, get_default_diagnostics_path},
+    file::{open_in_default_app, reveal_in_file_manager},
+    instance::{acquire_instance_lock, send_to_running_instance, InstanceRequest},
+    launch::sim_launch_thread,
+    logger::{get_recent_log_entries, init_logger},
+    metrics::init_metrics,
+    msfs::{check_if_msfs_running, msfs_watch_thread},
+    net::{
+        interface_watch_thread, ipv6_scope_ids, reachable_address, resolve_exposure,
+        ExposedInterface,
+    },
+    probe::{endpoint_probe_thread, probe_target},
+    reflector::run_reflector,
+    relay::{start_relay, AllowList, RelayRegistry},
+    simconnect::{
+        find_simconnect_xml_path, has_simconnect_backup, restore_simconnect_xml,
+        update_simconnect_config, SimConnectConfigResult,
+    },
+    udp::{udp_broadcast_thread, BroadcastCommand, BroadcastConfig, BroadcastStats},
 };
 
+use chrono::{DateTime, Local};
+use glyphon::{
+    Attrs, Buffer, Color, Family, FontSystem, Metrics, Shaping, Style, SwashCache, TextArea,
+    Weight, Wrap,
+};
 use tray_icon::menu::MenuEvent;
 use winit::{
-    dpi::{PhysicalPosition, PhysicalSize},
-    event::{Event, WindowEvent},
-    event_loop::{ControlFlow, EventLoop, EventLoopBuilder},
+    dpi::{LogicalPosition, LogicalSize},
+    event::{ElementState, Event, MouseButton, StartCause, WindowEvent},
+    event_loop::{ControlFlow, EventLoop, EventLoopBuilder, EventLoopProxy},
+    keyboard::{Key, NamedKey},
     window::{Theme, Window, WindowBuilder, WindowButtons},
 };
 
-use glyphon::{
-    Attrs, Buffer, Color, Family, Metrics, Resolution, Shaping, Style, TextArea, TextBounds, Weight,
+use std::{
+    cell::{Cell, RefCell},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant, UNIX_EPOCH},
 };
 
-use std::{sync::mpsc, thread};
-
-use crate::state::{AppState, AppStatus};
+use crate::{
+    cli::{attach_parent_console, parse_args, usage, Command},
+    settings::{get_settings_path, Settings, SimLauncher},
+    state::{
+        AppEvent, AppState, AppStatus, BroadcasterState, ConfigPhase, EndpointHealth,
+        RestartReason, SimLaunchPhase, StateEvent, StateRecord,
+    },
+};
 
 pub static APP_TITLE: &str = "FSRewire-client";
+static MSFS_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How long exiting waits for the withdrawal to go out.
+static WITHDRAWAL_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long the tray icon highlights a sent announcement.
+static PULSE_DURATION: Duration = Duration::from_millis(700);
+/// Keeps "last packet" in the tray tooltip current while nothing else changes.
+static TRAY_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+/// A broadcaster that stopped is started again after this delay, doubled with every failure.
+static BROADCAST_RETRY_MIN: Duration = Duration::from_secs(5);
+static BROADCAST_RETRY_MAX: Duration = Duration::from_secs(300);
+/// Autostart and SimConnect.xml changes made outside the client show up after this time.
+static HOST_STATE_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+/* Text slots of the status window, in logical pixels */
+static SLOT_HEADER: Slot = Slot::at(75.0, 20.0);
+static SLOT_STATUS: Slot = Slot::at(100.0, 90.0);
+static SLOT_MESSAGE: Slot = Slot {
+    right_margin: 20.0,
+    ..Slot::at(100.0, 125.0)
+};
+static SLOT_DETAIL: Slot = Slot {
+    right_margin: 20.0,
+    ..Slot::at(100.0, 160.0)
+};
+static SLOT_ENDPOINT: Slot = Slot {
+    right_margin: 20.0,
+    ..Slot::at(100.0, 185.0)
+};
+static SLOT_CLIENTS: Slot = Slot {
+    right_margin: 20.0,
+    ..Slot::at(100.0, 205.0)
+};
+static SLOT_INFO: Slot = Slot {
+    right_margin: 20.0,
+    ..Slot::at(40.0, 240.0)
+};
+/// Stops short of the version in the bottom right corner.
+static SLOT_EVENTS: Slot = Slot {
+    right_margin: 20.0,
+    bottom_margin: 25.0,
+    ..Slot::at(40.0, 345.0)
+};
+static SLOT_LOG: Slot = Slot {
+    right_margin: 20.0,
+    bottom_margin: 25.0,
+    ..Slot::at(20.0, 60.0)
+};
+static SLOT_VERSION: Slot = Slot {
+    left: Offset::End(120.0),
+    top: Offset::End(20.0),
+    right_margin: 0.0,
+    bottom_margin: 0.0,
+};
+/// Number of state changes listed in the status window.
+static STATUS_EVENT_ENTRIES: usize = 5;
+/// Number of log entries that fit into the status window.
+static LOG_VIEW_ENTRIES: usize = 10;
+
+#[derive(Clone, Copy, PartialEq)]
+enum WindowView {
+    Status,
+    Log,
+}
 
-async fn run(window: &Window, app_state: &mut AppState, event_loop: EventLoop<()>) {
+async fn run(
+    window: &Window,
+    app_state: &mut AppState,
+    settings: Settings,
+    event_loop: EventLoop<AppEvent>,
+) {
     let mut system_try = SystemTry::new();
-    let (udp_thread_sender, udp_thread_receiver) = mpsc::channel();
-
-    let (
-        device,
-        queue,
-        viewport,
-        mut font_system,
-        mut swash_cache,
-        mut text_atlas,
-        mut text_renderer,
-    ) = configure_wgpu(window).await;
+    let (probe_target_sender, probe_target_receiver) = mpsc::channel();
+    let (client_port_sender, client_port_receiver) = mpsc::channel();
+    let connected_clients: Arc<Mutex<Vec<ConnectedClient>>> = Arc::new(Mutex::new(Vec::new()));
+    let connected_clients = &connected_clients;
+    let event_loop_proxy = event_loop.create_proxy();
+
+    let mut renderer = create_renderer(window).await;
+    let has_window = renderer.is_available();
+    let mut font_system = FontSystem::new();
+    let mut swash_cache = SwashCache::new();
 
     let mut text_app_header = Buffer::new(&mut font_system, Metrics::new(22.0, 24.0));
     let mut text_app_version = Buffer::new(&mut font_system, Metrics::new(14.0, 16.0));
     let mut text_app_status = Buffer::new(&mut font_system, Metrics::new(22.0, 24.0));
     let mut text_app_message = Buffer::new(&mut font_system, Metrics::new(20.0, 22.0));
+    let mut text_app_detail = Buffer::new(&mut font_system, Metrics::new(16.0, 18.0));
+    let mut text_app_endpoint = Buffer::new(&mut font_system, Metrics::new(16.0, 18.0));
+    let mut text_app_clients = Buffer::new(&mut font_system, Metrics::new(14.0, 16.0));
+    let mut text_app_info = Buffer::new(&mut font_system, Metrics::new(13.0, 18.0));
+    let mut text_app_events = Buffer::new(&mut font_system, Metrics::new(12.0, 16.0));
+    let mut text_app_log = Buffer::new(&mut font_system, Metrics::new(12.0, 14.0));
 
-    let physical_width = window.inner_size().width;
-    let physical_height = window.inner_size().height;
+    /* Long paths and event lines are clipped at the window edge instead of pushing the rest down */
+    text_app_info.set_wrap(&mut font_system, Wrap::None);
+    text_app_events.set_wrap(&mut font_system, Wrap::None);
 
     {
         // text_app_header
-        text_app_header.set_size(
-            &mut font_system,
-            physical_width as f32,
-            physical_height as f32,
-        );
         text_app_header.set_text(
             &mut font_system,
             "Discovery Service for Flight Simulator Host",
@@ -77,11 +196,6 @@ async fn run(window: &Window, app_state: &mut AppState, event_loop: EventLoop<()
 
     {
         // text_app_status
-        text_app_status.set_size(
-            &mut font_system,
-            physical_width as f32,
-            physical_height as f32,
-        );
         text_app_status.set_text(
             &mut font_system,
             "Status:",
@@ -93,11 +207,6 @@ async fn run(window: &Window, app_state: &mut AppState, event_loop: EventLoop<()
 
     {
         // text_app_version
-        text_app_version.set_size(
-            &mut font_system,
-            physical_width as f32,
-            physical_height as f32,
-        );
         text_app_version.set_text(
             &mut font_system,
             RELESE_TAG,
@@ -107,180 +216,551 @@ async fn run(window: &Window, app_state: &mut AppState, event_loop: EventLoop<()
         text_app_version.set_redraw(false);
     }
 
-    text_app_message.set_size(
+    let broadcast_stats = Arc::new(Mutex::new(BroadcastStats::default()));
+    let broadcast_stats = &broadcast_stats;
+    let host_state = RefCell::new(HostState::read());
+    let host_state = &host_state;
+    let controls = RefCell::new(Controls::new(
         &mut font_system,
-        physical_width as f32,
-        physical_height as f32,
-    );
+        &[
+            (MENU_ITEM_COPY_CONNECTION_ID, "Copy address"),
+            (MENU_ITEM_RESTART_BROADCAST_ID, "Restart broadcast"),
+            (MENU_ITEM_REAPPLY_CONFIG_ID, "Apply config"),
+            (MENU_ITEM_RESTORE_CONFIG_ID, "Restore backup"),
+            (MENU_ITEM_OPEN_SETTINGS_ID, "Open settings"),
+        ],
+        40.0,
+        54.0,
+    ));
+    let controls = &controls;
+    let layout = Cell::new(Layout::new(window.inner_size(), window.scale_factor()));
+    let layout = &layout;
 
-    let mut redraw = |app_state: &AppState| {
-        let mut text_areas: Vec<TextArea> = Vec::new();
+    let mut redraw = |app_state: &AppState, window_view: WindowView| {
+        let layout = layout.get();
+
+        if !renderer.is_available() || layout.is_empty() {
+            return;
+        }
+
+        layout.fit(&mut font_system, &mut text_app_header, &SLOT_HEADER);
+        layout.fit(&mut font_system, &mut text_app_version, &SLOT_VERSION);
+
+        if window_view == WindowView::Log {
+            // text_app_log
+            layout.fit(&mut font_system, &mut text_app_log, &SLOT_LOG);
+            text_app_log.set_text(
+                &mut font_system,
+                &get_recent_log_entries(LOG_VIEW_ENTRIES).join("\n"),
+                Attrs::new().family(Family::Monospace),
+                Shaping::Advanced,
+            );
+        } else {
+            layout.fit(&mut font_system, &mut text_app_status, &SLOT_STATUS);
 
-        {
             // text_app_message
+            layout.fit(&mut font_system, &mut text_app_message, &SLOT_MESSAGE);
             text_app_message.set_text(
                 &mut font_system,
-                &app_state.msg_text,
+                app_state.message(),
                 Attrs::new().family(Family::SansSerif).style(Style::Italic),
                 Shaping::Advanced,
             );
 
-            text_areas.push(TextArea {
-                buffer: &text_app_message,
-                left: 100.0,
-                top: 125.0,
-                scale: 1.0,
-                bounds: TextBounds {
-                    left: 0,
-                    top: 0,
-                    right: physical_width as i32,
-                    bottom: physical_height as i32,
-                },
-                default_color: Color::rgb(220, 220, 220),
-            });
-        }
+            // text_app_detail
+            layout.fit(&mut font_system, &mut text_app_detail, &SLOT_DETAIL);
+            text_app_detail.set_text(
+                &mut font_system,
+                &app_state.detail(),
+                Attrs::new().family(Family::SansSerif),
+                Shaping::Advanced,
+            );
 
-        text_areas.push(TextArea {
-            buffer: &text_app_header,
-            left: 75.0,
-            top: 20.0,
-            scale: 1.0,
-            bounds: TextBounds {
-                left: 0,
-                top: 0,
-                right: physical_width as i32,
-                bottom: physical_height as i32,
-            },
-            default_color: Color::rgb(220, 220, 220),
-        });
-
-        text_areas.push(TextArea {
-            buffer: &text_app_status,
-            left: 100.0,
-            top: 90.0,
-            scale: 1.0,
-            bounds: TextBounds {
-                left: 0,
-                top: 0,
-                right: physical_width as i32,
-                bottom: physical_height as i32,
-            },
-            default_color: Color::rgb(220, 220, 220),
-        });
-
-        text_areas.push(TextArea {
-            buffer: &text_app_version,
-            left: 480.0,
-            top: 220.0,
-            scale: 1.0,
-            bounds: TextBounds {
-                left: 0,
-                top: 0,
-                right: physical_width as i32,
-                bottom: physical_height as i32,
-            },
-            default_color: Color::rgb(100, 100, 100),
-        });
-
-        text_renderer
-            .prepare(
-                &device,
-                &queue,
+            // text_app_endpoint
+            layout.fit(&mut font_system, &mut text_app_endpoint, &SLOT_ENDPOINT);
+            text_app_endpoint.set_text(
                 &mut font_system,
-                &mut text_atlas,
-                Resolution {
-                    width: physical_width,
-                    height: physical_height,
-                },
-                text_areas,
-                &mut swash_cache,
-            )
-            .unwrap();
+                app_state.endpoint_health.label(),
+                Attrs::new().family(Family::SansSerif),
+                Shaping::Advanced,
+            );
 
-        let frame = viewport.get_current_texture().unwrap();
+            // text_app_clients
+            let clients = connected_clients
+                .lock()
+                .map(|clients| describe_clients(&clients))
+                .unwrap_or_default();
 
-        let view = frame
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+            layout.fit(&mut font_system, &mut text_app_clients, &SLOT_CLIENTS);
+            text_app_clients.set_text(
+                &mut font_system,
+                &clients,
+                Attrs::new().family(Family::SansSerif),
+                Shaping::Advanced,
+            );
 
-        let mut encoder =
-            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+            // text_app_info
+            let info = broadcast_stats
+                .lock()
+                .map(|stats| describe_status_info(app_state, &stats, &host_state.borrow()))
+                .unwrap_or_default();
 
-        {
-            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: None,
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.02,
-                            g: 0.02,
-                            b: 0.02,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
+            layout.fit(&mut font_system, &mut text_app_info, &SLOT_INFO);
+            text_app_info.set_text(
+                &mut font_system,
+                &info,
+                Attrs::new().family(Family::Monospace),
+                Shaping::Advanced,
+            );
+
+            // text_app_events
+            let events: Vec<String> = app_state
+                .history()
+                .rev()
+                .take(STATUS_EVENT_ENTRIES)
+                .map(StateRecord::describe)
+                .collect();
+
+            layout.fit(&mut font_system, &mut text_app_events, &SLOT_EVENTS);
+            text_app_events.set_text(
+                &mut font_system,
+                &format!("Recent events\n{}", events.join("\n")),
+                Attrs::new().family(Family::Monospace),
+                Shaping::Advanced,
+            );
+        }
+
+        let controls = controls.borrow();
+        let mut rects = Vec::new();
+        let mut text_areas: Vec<TextArea> =
+            vec![layout.text_area(&text_app_header, &SLOT_HEADER, Color::rgb(220, 220, 220))];
+
+        if window_view == WindowView::Status {
+            rects = controls.rects(&layout);
+            text_areas.extend(controls.text_areas(&layout));
+        }
+
+        if window_view == WindowView::Log {
+            text_areas.push(layout.text_area(&text_app_log, &SLOT_LOG, Color::rgb(180, 180, 180)));
+        } else {
+            text_areas.extend([
+                layout.text_area(&text_app_status, &SLOT_STATUS, Color::rgb(220, 220, 220)),
+                layout.text_area(&text_app_message, &SLOT_MESSAGE, Color::rgb(220, 220, 220)),
+                layout.text_area(&text_app_detail, &SLOT_DETAIL, Color::rgb(150, 150, 150)),
+                layout.text_area(
+                    &text_app_endpoint,
+                    &SLOT_ENDPOINT,
+                    Color::rgb(150, 150, 150),
+                ),
+                layout.text_area(&text_app_clients, &SLOT_CLIENTS, Color::rgb(150, 150, 150)),
+                layout.text_area(&text_app_info, &SLOT_INFO, Color::rgb(180, 180, 180)),
+                layout.text_area(&text_app_events, &SLOT_EVENTS, Color::rgb(130, 130, 130)),
+            ]);
+        }
+
+        text_areas.push(layout.text_area(
+            &text_app_version,
+            &SLOT_VERSION,
+            Color::rgb(100, 100, 100),
+        ));
+
+        if let Err(error) = renderer.draw(
+            &mut font_system,
+            &mut swash_cache,
+            &layout,
+            text_areas,
+            &rects,
+        ) {
+            log::warn!("{}", error);
+        }
+    };
+
+    let mut window_view = WindowView::Status;
+    let status_snapshot = Arc::new(Mutex::new(app_state.snapshot()));
+
+    let last_packet_at: Cell<Option<Instant>> = Cell::new(None);
+    let last_packet_at = &last_packet_at;
 
-            text_renderer.render(&text_atlas, &mut rpass).unwrap();
+    let mut render_state = |app_state: &AppState, window_view: WindowView| {
+        if let Ok(mut snapshot) = status_snapshot.lock() {
+            *snapshot = app_state.snapshot();
         }
 
-        queue.submit(Some(encoder.finish()));
-        frame.present();
+        let since_last_packet = last_packet_at.get().map(|sent_at| sent_at.elapsed());
+        let host_state = host_state.borrow();
+        let menu_state = TryMenuState {
+            has_config_file: host_state.simconnect_xml_path.is_some(),
+            has_config_backup: host_state.has_config_backup,
+            is_autostart_enabled: host_state.is_autostart_enabled,
+            seconds_since_last_packet: since_last_packet.map(|elapsed| elapsed.as_secs()),
+            client_count: connected_clients
+                .lock()
+                .map(|clients| clients.len())
+                .unwrap_or(0),
+            is_pulsing: since_last_packet.is_some_and(|elapsed| elapsed < PULSE_DURATION),
+        };
 
-        text_atlas.trim();
+        {
+            /* Same availability as the tray entries */
+            let mut controls = controls.borrow_mut();
+            controls.set_enabled(
+                MENU_ITEM_COPY_CONNECTION_ID,
+                app_state.connection_string.is_some() && app_state.status() != AppStatus::Error,
+            );
+            controls.set_enabled(MENU_ITEM_RESTART_BROADCAST_ID, app_state.can_broadcast());
+            controls.set_enabled(
+                MENU_ITEM_RESTORE_CONFIG_ID,
+                menu_state.has_config_backup && app_state.config_phase != ConfigPhase::Restored,
+            );
+        }
+
+        system_try.update(app_state, &menu_state);
+        redraw(app_state, window_view);
     };
 
-    redraw(&app_state);
+    render_state(app_state, window_view);
 
-    window.set_visible(true);
-    window.focus_window();
+    /* Without a renderer the window stays hidden, the tray and the broadcaster work regardless */
+    if has_window {
+        window.set_visible(true);
+        window.focus_window();
+    }
 
     let is_msfs_running = check_if_msfs_running();
+    app_state.apply(StateEvent::SimPresenceChanged(is_msfs_running));
+
+    /* Subscribed after the initial presence, which isn't a change worth a notification */
+    let state_records = app_state.subscribe();
+    let notification_settings = settings.notifications.clone();
+    thread::spawn(move || notification_thread(notification_settings, state_records));
+
+    let exposed_interface = resolve_exposure(&settings.exposure);
+    let simconnect_config_result = exposed_interface.clone().and_then(|interface| {
+        update_simconnect_config(
+            &simconnect_server_address(&settings, &interface),
+            &settings,
+            is_msfs_running,
+        )
+        .map(|config| (interface, config))
+    });
 
-    let simconnect_config_result = update_simconnect_config();
+    let mut broadcast_command_sender = None;
+    let mut broadcast_retry_at: Option<Instant> = None;
+    let mut broadcast_retry_delay = BROADCAST_RETRY_MIN;
+    let mut current_config = None;
+    let relay_registry = Arc::new(RelayRegistry::default());
+    let mut relay_target = None;
+    /* Taken by the endpoint watchers once a configuration is applied, possibly only after a retry */
+    let mut endpoint_receivers = Some((probe_target_receiver, client_port_receiver));
 
     match simconnect_config_result {
-        Ok(config) => {
-            if (config.is_changed && is_msfs_running) {
-                system_try.set_status(AppStatus::Warning);
-                app_state.status = AppStatus::Warning;
-                app_state.msg_text =
-                    "⭕ Run this client before the simulator is started.".to_string();
-            } else {
-                thread::spawn(move || udp_broadcast_thread(udp_thread_sender, config.port));
+        Ok((interface, config)) => {
+            app_state.apply(StateEvent::ConfigApplied {
+                address: config.address.clone(),
+                port: config.port.clone(),
+                connection_string: connection_string(&settings, &interface, &config),
+                port_decision: config.port_decision.clone(),
+                restart_required: (config.is_changed && is_msfs_running)
+                    .then_some(RestartReason::StartOrder),
+            });
+
+            if app_state.can_broadcast() {
+                broadcast_command_sender = Some(start_broadcasting(
+                    &event_loop_proxy,
+                    &settings,
+                    interface,
+                    &config,
+                    broadcast_stats,
+                    app_state.is_withdrawn(),
+                ));
+            }
+
+            if let Some((probe_target_receiver, client_port_receiver)) = endpoint_receivers.take() {
+                relay_target = start_endpoint_watchers(
+                    &event_loop_proxy,
+                    &settings,
+                    &config,
+                    probe_target_receiver,
+                    client_port_receiver,
+                    &relay_registry,
+                    connected_clients,
+                );
             }
+
+            current_config = Some((interface, config));
         }
-        Err(_) => {
-            system_try.set_status(AppStatus::Error);
-            app_state.status = AppStatus::Error;
-            app_state.msg_text = format!("🔴 Fatal error during SimConnect configuration.");
+        Err(error) => {
+            log::error!("SimConnect configuration failed: {}", error);
+            app_state.apply(StateEvent::ConfigFailed(error));
         }
     }
 
+    /* Applying the config may have created SimConnect.xml's backup */
+    host_state.replace(HostState::read());
+
+    /* Also watched when the interface has no address yet, e.g. DHCP still pending at login */
+    let proxy = event_loop_proxy.clone();
+    let exposure = settings.exposure.clone();
+    let poll_interval = Duration::from_secs(settings.interface_poll_secs);
+    let initial_interface = exposed_interface.ok();
+    thread::spawn(move || {
+        interface_watch_thread(proxy, exposure, initial_interface, poll_interval)
+    });
+
+    /* Launched only now, so the simulator loads the configuration written above */
+    if settings.launch.launcher != SimLauncher::Off {
+        if is_msfs_running {
+            log::info!("Simulator is already running, not launching it");
+        } else if current_config.is_none() {
+            app_state.apply(StateEvent::SimLaunchChanged(SimLaunchPhase::Failed(
+                "Not launched, SimConnect.xml couldn't be configured.".to_string(),
+            )));
+        } else {
+            let proxy = event_loop_proxy.clone();
+            let launcher = settings.launch.launcher.clone();
+            let timeout = Duration::from_secs(settings.launch.timeout_secs);
+            thread::spawn(move || sim_launch_thread(proxy, launcher, timeout));
+        }
+    }
+
+    let proxy = event_loop_proxy.clone();
+    thread::spawn(move || msfs_watch_thread(proxy, MSFS_POLL_INTERVAL));
+
+    if settings.api.enabled {
+        let events = EventHub::new(app_state.history());
+        let state_records = app_state.subscribe();
+        let hub = events.clone();
+        thread::spawn(move || event_hub_thread(hub, state_records));
+
+        let proxy = event_loop_proxy.clone();
+        let api_context = ApiContext {
+            status: status_snapshot.clone(),
+            broadcast_stats: broadcast_stats.clone(),
+            restart_broadcast: Arc::new(move || {
+                proxy.send_event(AppEvent::RestartBroadcast).is_ok()
+            }),
+            events,
+            clients: connected_clients.clone(),
+        };
+
+        if let Err(error) = start_api_server(&settings.api, api_context) {
+            log::error!("{}", error);
+        }
+    }
+
+    if settings.metrics.enabled {
+        if let Err(error) = start_metrics_server(&settings.metrics) {
+            log::error!("{}", error);
+        }
+    }
+
+    render_state(app_state, window_view);
+
     let menu_channel = MenuEvent::receiver();
+    let mut tray_refreshed_at = Instant::now();
+    let mut host_state_read_at = Instant::now();
+
+    let mut is_shift_pressed = false;
+
+    event_loop.run(move |event: Event<AppEvent>, event_loop| {
+        let mut activated_control = None;
+        let mut redraw_controls = false;
+
+        if let Event::NewEvents(StartCause::ResumeTimeReached { .. }) = event {
+            tray_refreshed_at = Instant::now();
 
-    event_loop.run(move |event: Event<()>, event_loop| {
-        event_loop.set_control_flow(ControlFlow::Wait);
+            if host_state_read_at.elapsed() >= HOST_STATE_REFRESH_INTERVAL {
+                host_state.replace(HostState::read());
+                host_state_read_at = Instant::now();
+            }
+
+            render_state(app_state, window_view);
+        }
 
-        if let Event::WindowEvent { window_id, event } = event {
+        /* Wake up to end the activity pulse or to refresh the tooltip */
+        let pulse_ends_at = last_packet_at
+            .get()
+            .map(|sent_at| sent_at + PULSE_DURATION)
+            .filter(|ends_at| *ends_at > Instant::now());
+        event_loop.set_control_flow(ControlFlow::WaitUntil(
+            pulse_ends_at.unwrap_or(tray_refreshed_at + TRAY_REFRESH_INTERVAL),
+        ));
+
+        if let Event::WindowEvent { window_id, event } = &event {
             match event {
                 WindowEvent::CloseRequested => {
                     window.set_visible(false);
                 }
+                WindowEvent::Resized(size) => {
+                    layout.set(Layout {
+                        size: *size,
+                        ..layout.get()
+                    });
+                    render_state(app_state, window_view);
+                }
+                WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                    layout.set(Layout::new(window.inner_size(), *scale_factor));
+                    render_state(app_state, window_view);
+                }
+                /* Buttons are only shown, and operated, in the status view */
+                _ if window_view != WindowView::Status => {}
+                WindowEvent::CursorMoved { position, .. } => {
+                    let scale_factor = layout.get().scale_factor;
+
+                    if controls.borrow_mut().pointer_moved(
+                        position.x as f32 / scale_factor,
+                        position.y as f32 / scale_factor,
+                    ) {
+                        redraw_controls = true;
+                    }
+                }
+                WindowEvent::CursorLeft { .. } | WindowEvent::Focused(false) => {
+                    redraw_controls = controls.borrow_mut().pointer_left();
+                }
+                WindowEvent::MouseInput {
+                    state,
+                    button: MouseButton::Left,
+                    ..
+                } => {
+                    let mut controls = controls.borrow_mut();
+
+                    redraw_controls = match state {
+                        ElementState::Pressed => controls.pointer_pressed(),
+                        ElementState::Released => {
+                            activated_control = controls.pointer_released();
+                            true
+                        }
+                    };
+                }
+                WindowEvent::ModifiersChanged(modifiers) => {
+                    is_shift_pressed = modifiers.state().shift_key();
+                }
+                WindowEvent::KeyboardInput { event, .. } if event.state == ElementState::Pressed => {
+                    let mut controls = controls.borrow_mut();
+
+                    match &event.logical_key {
+                        Key::Named(NamedKey::Tab) => {
+                            redraw_controls = controls.focus_next(is_shift_pressed);
+                        }
+                        Key::Named(NamedKey::Enter | NamedKey::Space) => {
+                            activated_control = controls.focused_id();
+                        }
+                        Key::Named(NamedKey::Escape) => {
+                            redraw_controls = controls.clear_focus();
+                        }
+                        _ => {}
+                    }
+                }
                 _ => {}
             }
         }
 
-        if let Ok(event) = menu_channel.try_recv() {
-            if event.id.0 == MENU_ITEM_EXIT_ID {
+        if redraw_controls {
+            render_state(app_state, window_view);
+        }
+
+        /* Window buttons run the same actions as the tray menu entries with their id */
+        let menu_id = menu_channel.try_recv().ok().map(|menu_event| menu_event.id.0);
+
+        for id in [menu_id, activated_control.map(str::to_string)]
+            .into_iter()
+            .flatten()
+        {
+            if id == MENU_ITEM_EXIT_ID {
+                log::info!("Exit requested from the tray");
+
+                if let Some(sender) = &broadcast_command_sender {
+                    let (done_sender, done_receiver) = mpsc::channel();
+
+                    if sender
+                        .send(BroadcastCommand::Withdraw(Some(done_sender)))
+                        .is_ok()
+                    {
+                        done_receiver.recv_timeout(WITHDRAWAL_TIMEOUT).ok();
+                    }
+                }
+
+                log::logger().flush();
                 std::process::exit(0);
-            } else if event.id.0 == MENU_ITEM_STATUS_ID {
+            } else if id == MENU_ITEM_DIAGNOSTICS_ID {
+                export_diagnostics_in_background(
+                    &event_loop_proxy,
+                    &settings,
+                    app_state,
+                    get_default_diagnostics_path(),
+                    None,
+                );
+            } else if id == MENU_ITEM_COPY_CONNECTION_ID {
+                if let Some(connection_string) = &app_state.connection_string {
+                    if let Err(e) = arboard::Clipboard::new()
+                        .and_then(|mut clipboard| clipboard.set_text(connection_string.clone()))
+                    {
+                        log::error!("Unable to copy the connection string: {}", e);
+                    }
+                }
+            } else if id == MENU_ITEM_RESTART_BROADCAST_ID {
+                event_loop_proxy.send_event(AppEvent::RestartBroadcast).ok();
+            } else if id == MENU_ITEM_REAPPLY_CONFIG_ID {
+                event_loop_proxy.send_event(AppEvent::ReapplyConfig).ok();
+            } else if id == MENU_ITEM_OPEN_CONFIG_FOLDER_ID {
+                if let Some(xml_path) = find_simconnect_xml_path() {
+                    if let Err(error) = reveal_in_file_manager(std::path::Path::new(&xml_path)) {
+                        log::error!("{}", error);
+                    }
+                }
+            } else if id == MENU_ITEM_OPEN_SETTINGS_ID {
+                if let Err(error) = get_settings_path().and_then(|path| open_in_default_app(&path)) {
+                    log::error!("{}", error);
+                }
+            } else if id == MENU_ITEM_RESTORE_CONFIG_ID {
+                match restore_simconnect_xml() {
+                    Ok(_) => {
+                        /* The original config may not expose SimConnect, so announcing stops */
+                        if let Some(sender) = broadcast_command_sender.take() {
+                            sender.send(BroadcastCommand::Withdraw(None)).ok();
+                        }
+
+                        app_state.apply(StateEvent::BroadcasterChanged(BroadcasterState::Idle));
+                        app_state.apply(StateEvent::ConfigRestored);
+                    }
+                    Err(error) => log::error!("{}", error),
+                }
+
+                host_state.replace(HostState::read());
+
+                render_state(app_state, window_view);
+            } else if id == MENU_ITEM_PAUSE_ID {
+                let is_paused = !app_state.is_paused;
+                app_state.apply(StateEvent::AnnouncementsPaused(is_paused));
+
+                /* A simulator that stopped stays withdrawn when unpausing */
+                if let Some(sender) = &broadcast_command_sender {
+                    if is_paused {
+                        sender.send(BroadcastCommand::Withdraw(None)).ok();
+                    } else if !app_state.is_withdrawn() {
+                        sender.send(BroadcastCommand::Announce).ok();
+                    }
+                }
+
+                render_state(app_state, window_view);
+            } else if id == MENU_ITEM_AUTOSTART_ID {
+                if let Err(error) = set_autostart(!is_autostart_enabled()) {
+                    log::error!("{}", error);
+                }
+
+                host_state.borrow_mut().is_autostart_enabled = is_autostart_enabled();
+
+                render_state(app_state, window_view);
+            } else if (id == MENU_ITEM_STATUS_ID || id == MENU_ITEM_LOG_ID) && !has_window {
+                log::warn!("The status window is unavailable, nothing can render it");
+            } else if id == MENU_ITEM_STATUS_ID || id == MENU_ITEM_LOG_ID {
+                window_view = if id == MENU_ITEM_LOG_ID {
+                    WindowView::Log
+                } else {
+                    WindowView::Status
+                };
+                render_state(app_state, window_view);
+
                 if window.is_minimized().is_some() && window.is_minimized().unwrap() == true {
                     window.set_visible(false);
                 }
@@ -293,50 +773,639 @@ async fn run(window: &Window, app_state: &mut AppState, event_loop: EventLoop<()
             }
         }
 
-        match udp_thread_receiver.try_recv() {
-            Ok(message) => {
-                if message == UDP_THREAD_STATUS_ERROR {
-                    system_try.set_status(AppStatus::Error);
-                    app_state.status = AppStatus::Error;
-                    app_state.msg_text = "🔴 Fatal error during data broadcasting.".to_string();
-
-                    redraw(&app_state);
-                } else if message == UDP_THREAD_STATUS_OK && app_state.status != AppStatus::Running
-                {
-                    system_try.set_status(AppStatus::Running);
-                    app_state.status = AppStatus::Running;
-                    app_state.msg_text = "✅ Client is working normally.".to_string();
-
-                    redraw(&app_state);
+        if let Event::UserEvent(app_event) = event {
+            let is_changed = match app_event {
+                AppEvent::BroadcastOk => {
+                    broadcast_retry_delay = BROADCAST_RETRY_MIN;
+                    app_state.apply(StateEvent::BroadcasterChanged(BroadcasterState::Running))
+                }
+                AppEvent::AnnouncementSent => {
+                    last_packet_at.set(Some(Instant::now()));
+                    true
+                }
+                AppEvent::AnnouncementFailed => {
+                    app_state.apply(StateEvent::BroadcasterChanged(BroadcasterState::Failed))
                 }
+                AppEvent::BroadcastError => {
+                    /* The broadcaster stopped, a new one is started below once the delay passed */
+                    broadcast_command_sender = None;
+                    broadcast_retry_at = Some(Instant::now() + broadcast_retry_delay);
+                    log::warn!(
+                        "Restarting the broadcaster in {}s",
+                        broadcast_retry_delay.as_secs()
+                    );
+
+                    let proxy = event_loop_proxy.clone();
+                    let delay = broadcast_retry_delay;
+                    thread::spawn(move || {
+                        thread::sleep(delay);
+                        proxy.send_event(AppEvent::RetryBroadcast).ok();
+                    });
+                    broadcast_retry_delay = (broadcast_retry_delay * 2).min(BROADCAST_RETRY_MAX);
+
+                    app_state.apply(StateEvent::BroadcasterChanged(BroadcasterState::Failed))
+                }
+                AppEvent::RetryBroadcast => false,
+                AppEvent::EndpointProbed(result) => app_state.apply(
+                    StateEvent::EndpointHealthChanged(EndpointHealth::from(&result)),
+                ),
+                AppEvent::RestartBroadcast => {
+                    log::info!("Broadcast restart requested");
+
+                    /* Dropping the sender stops the running broadcaster, a new one is started below */
+                    broadcast_command_sender = None;
+                    broadcast_retry_at = None;
+                    app_state.apply(StateEvent::BroadcasterChanged(BroadcasterState::Idle))
+                }
+                AppEvent::SimPresenceChanged(is_running) => {
+                    let was_withdrawn = app_state.is_withdrawn();
+                    let is_changed = app_state.apply(StateEvent::SimPresenceChanged(is_running));
+
+                    if let Some(sender) = &broadcast_command_sender {
+                        match (was_withdrawn, app_state.is_withdrawn()) {
+                            (false, true) => {
+                                sender.send(BroadcastCommand::Withdraw(None)).ok();
+                            }
+                            (_, false) if is_running => {
+                                sender.send(BroadcastCommand::Announce).ok();
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    is_changed
+                }
+                AppEvent::SimLaunchChanged(phase) => {
+                    app_state.apply(StateEvent::SimLaunchChanged(phase))
+                }
+                AppEvent::InstanceRequest(request, reply_sender) => {
+                    let reply = match request {
+                        InstanceRequest::Show if !has_window => {
+                            Err("The status window is unavailable, nothing can render it."
+                                .to_string())
+                        }
+                        InstanceRequest::Show => {
+                            window_view = WindowView::Status;
+                            window.set_minimized(false);
+                            window.set_visible(true);
+                            window.focus_window();
+                            Ok("Status window shown.".to_string())
+                        }
+                        InstanceRequest::Diagnostics { output } => {
+                            /* Answered once the bundle is written */
+                            export_diagnostics_in_background(
+                                &event_loop_proxy,
+                                &settings,
+                                app_state,
+                                output,
+                                Some(reply_sender),
+                            );
+                            return;
+                        }
+                    };
+
+                    reply_sender.send(reply).ok();
+                    true
+                }
+                AppEvent::DiagnosticsExported {
+                    output,
+                    result,
+                    reply_sender,
+                } => {
+                    match reply_sender {
+                        Some(reply_sender) => {
+                            reply_sender
+                                .send(result.map(|_| {
+                                    format!("Diagnostics bundle written to {}", output.display())
+                                }))
+                                .ok();
+                        }
+                        None => {
+                            if let Err(error) =
+                                result.and_then(|_| reveal_in_file_manager(&output))
+                            {
+                                log::error!("Diagnostics export failed: {}", error);
+                            }
+                        }
+                    }
+
+                    false
+                }
+                AppEvent::ClientsChanged(new_addresses) => {
+                    /* Notified by the notification thread, showing one blocks */
+                    app_state.apply(StateEvent::ClientsConnected(new_addresses));
+                    true
+                }
+                app_event @ (AppEvent::ExposedInterfaceChanged(_) | AppEvent::ReapplyConfig) => {
+                    let is_msfs_running = check_if_msfs_running();
+
+                    let (interface, restart_reason) = match app_event {
+                        AppEvent::ExposedInterfaceChanged(interface) => {
+                            (Ok(interface), RestartReason::AddressChanged)
+                        }
+                        _ => {
+                            log::info!("Re-applying SimConnect config");
+                            (
+                                resolve_exposure(&settings.exposure),
+                                RestartReason::ConfigReapplied,
+                            )
+                        }
+                    };
+
+                    let config_result = interface.and_then(|interface| {
+                        update_simconnect_config(
+                            &simconnect_server_address(&settings, &interface),
+                            &settings,
+                            is_msfs_running,
+                        )
+                        .map(|config| (interface, config))
+                    });
+                    host_state.replace(HostState::read());
+
+                    match config_result {
+                        Ok((interface, config)) => {
+                            /* Port, IPv6 endpoint and interface may all differ, a new broadcaster starts below */
+                            if let Some(sender) = broadcast_command_sender.take() {
+                                sender.send(BroadcastCommand::Withdraw(None)).ok();
+                                app_state.apply(StateEvent::BroadcasterChanged(
+                                    BroadcasterState::Idle,
+                                ));
+                            }
+
+                            if let Some((probe_target_receiver, client_port_receiver)) =
+                                endpoint_receivers.take()
+                            {
+                                relay_target = start_endpoint_watchers(
+                                    &event_loop_proxy,
+                                    &settings,
+                                    &config,
+                                    probe_target_receiver,
+                                    client_port_receiver,
+                                    &relay_registry,
+                                    connected_clients,
+                                );
+                            } else if let Some(target) =
+                                probe_target(&config.address, &config.port)
+                            {
+                                probe_target_sender.send(target).ok();
+                                client_port_sender.send(target.port()).ok();
+
+                                if let Some(Ok(mut relay_target)) =
+                                    relay_target.as_ref().map(|relay_target| relay_target.lock())
+                                {
+                                    *relay_target = target;
+                                }
+                            }
+
+                            /* Behind the relay SimConnect stays on loopback, so nothing is rewritten */
+                            let is_changed = app_state.apply(StateEvent::ConfigApplied {
+                                address: config.address.clone(),
+                                port: config.port.clone(),
+                                connection_string: connection_string(
+                                    &settings, &interface, &config,
+                                ),
+                                port_decision: config.port_decision.clone(),
+                                restart_required: (config.is_changed && is_msfs_running)
+                                    .then_some(restart_reason),
+                            });
+
+                            current_config = Some((interface, config));
+
+                            is_changed
+                        }
+                        Err(error) => {
+                            log::error!("SimConnect configuration failed: {}", error);
+                            app_state.apply(StateEvent::ConfigFailed(error))
+                        }
+                    }
+                }
+            };
+
+            /* Broadcasting held back by the start order warning begins once the simulator restarted */
+            let is_retry_due = broadcast_retry_at.is_none_or(|retry_at| Instant::now() >= retry_at);
+
+            if broadcast_command_sender.is_none() && app_state.can_broadcast() && is_retry_due {
+                if let Some((interface, config)) = &current_config {
+                    broadcast_retry_at = None;
+                    broadcast_command_sender = Some(start_broadcasting(
+                        &event_loop_proxy,
+                        &settings,
+                        *interface,
+                        config,
+                        broadcast_stats,
+                        app_state.is_withdrawn(),
+                    ));
+                }
+            }
+
+            if is_changed {
+                render_state(app_state, window_view);
             }
-            _ => {}
         }
     });
 }
 
+/// Registry and filesystem lookups shown in the tray and the window.
+/// Read again after the actions changing them and on a slow tick, not on every redraw.
+struct HostState {
+    simconnect_xml_path: Option<String>,
+    has_config_backup: bool,
+    is_autostart_enabled: bool,
+}
+
+impl HostState {
+    fn read() -> Self {
+        HostState {
+            simconnect_xml_path: find_simconnect_xml_path(),
+            has_config_backup: has_simconnect_backup(),
+            is_autostart_enabled: is_autostart_enabled(),
+        }
+    }
+}
+
+/// Configuration and broadcast lines of the status window.
+fn describe_status_info(
+    app_state: &AppState,
+    stats: &BroadcastStats,
+    host_state: &HostState,
+) -> String {
+    let endpoint = match (&app_state.simconnect_address, &app_state.simconnect_port) {
+        (Some(address), Some(port)) => match &app_state.connection_string {
+            Some(connection_string) if *connection_string != format!("{}:{}", address, port) => {
+                format!("{}:{} (announced as {})", address, port, connection_string)
+            }
+            _ => format!("{}:{}", address, port),
+        },
+        _ => "-".to_string(),
+    };
+
+    let interfaces = if stats.interfaces.is_empty() {
+        "-".to_string()
+    } else {
+        stats
+            .interfaces
+            .iter()
+            .map(|interface| match &interface.last_error {
+                Some(_) => format!("{} (failing)", interface.interface),
+                None => interface.interface.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let last_packet = match stats.last_sent_at {
+        Some(sent_at) => {
+            let sent_at = UNIX_EPOCH + Duration::from_secs(sent_at);
+            format!(
+                "{} ({}s ago)",
+                DateTime::<Local>::from(sent_at).format("%H:%M:%S"),
+                sent_at.elapsed().unwrap_or_default().as_secs()
+            )
+        }
+        None => "-".to_string(),
+    };
+
+    [
+        (
+            "SimConnect.xml",
+            host_state
+                .simconnect_xml_path
+                .clone()
+                .unwrap_or("-".to_string()),
+        ),
+        ("Endpoint", endpoint),
+        ("Interfaces", interfaces),
+        ("Last packet", last_packet),
+        ("Simulator", app_state.sim_presence.label().to_string()),
+    ]
+    .iter()
+    .map(|(label, value)| format!("{:<16}{}", label, value))
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+/// Address written to SimConnect.xml, loopback when the simulator is only reached through the relay.
+fn simconnect_server_address(settings: &Settings, interface: &ExposedInterface) -> String {
+    if settings.is_sim_behind_relay() {
+        Ipv4Addr::LOCALHOST.to_string()
+    } else {
+        interface.address.to_string()
+    }
+}
+
+fn start_relay_listener(
+    settings: &Settings,
+    target: Arc<Mutex<SocketAddr>>,
+    registry: Arc<RelayRegistry>,
+) -> Result<(), String> {
+    let bind_address: IpAddr = settings.relay.bind.parse().map_err(|e| {
+        format!(
+            "Invalid relay bind address '{}': {}",
+            settings.relay.bind, e
+        )
+    })?;
+    let allow_list = AllowList::parse(&settings.relay.allow)?;
+
+    start_relay(
+        SocketAddr::new(bind_address, settings.relay.port),
+        target,
+        allow_list,
+        registry,
+    )
+}
+
+/// Writes the diagnostics bundle on a worker thread, the result comes back as `AppEvent::DiagnosticsExported`.
+fn export_diagnostics_in_background(
+    proxy: &EventLoopProxy<AppEvent>,
+    settings: &Settings,
+    app_state: &AppState,
+    output: PathBuf,
+    reply_sender: Option<mpsc::Sender<Result<String, String>>>,
+) {
+    let proxy = proxy.clone();
+    let settings = settings.clone();
+    let history: Vec<StateRecord> = app_state.history().cloned().collect();
+
+    thread::spawn(move || {
+        let result = export_diagnostics(&output, &settings, Some(&history));
+
+        proxy
+            .send_event(AppEvent::DiagnosticsExported {
+                output,
+                result,
+                reply_sender,
+            })
+            .ok();
+    });
+}
+
+/// Starts the probe, relay and client watch threads for the applied configuration, returns the relay's target.
+fn start_endpoint_watchers(
+    proxy: &EventLoopProxy<AppEvent>,
+    settings: &Settings,
+    config: &SimConnectConfigResult,
+    probe_target_receiver: mpsc::Receiver<SocketAddr>,
+    client_port_receiver: mpsc::Receiver<u16>,
+    relay_registry: &Arc<RelayRegistry>,
+    connected_clients: &Arc<Mutex<Vec<ConnectedClient>>>,
+) -> Option<Arc<Mutex<SocketAddr>>> {
+    let mut relay_target = None;
+
+    if settings.probe.enabled {
+        if let Some(target) = probe_target(&config.address, &config.port) {
+            let proxy = proxy.clone();
+            let interval = Duration::from_secs(settings.probe.interval_secs);
+            let timeout = Duration::from_millis(settings.probe.timeout_ms);
+            let handshake = settings.probe.handshake;
+            thread::spawn(move || {
+                endpoint_probe_thread(
+                    proxy,
+                    probe_target_receiver,
+                    target,
+                    interval,
+                    timeout,
+                    handshake,
+                )
+            });
+        }
+    }
+
+    if settings.relay.enabled {
+        if let Some(target) = probe_target(&config.address, &config.port) {
+            let target = Arc::new(Mutex::new(target));
+
+            match start_relay_listener(settings, target.clone(), relay_registry.clone()) {
+                Ok(_) => relay_target = Some(target),
+                Err(error) => log::error!("{}", error),
+            }
+        }
+    }
+
+    if settings.clients.enabled {
+        if let Ok(port) = config.port.parse() {
+            let proxy = proxy.clone();
+            let relay = relay_registry.clone();
+            let clients = connected_clients.clone();
+            let interval = Duration::from_secs(settings.clients.poll_secs);
+            thread::spawn(move || {
+                client_watch_thread(proxy, client_port_receiver, port, relay, clients, interval)
+            });
+        }
+    }
+
+    relay_target
+}
+
+/// Port in the announcement, clients can't reach a loopback SimConnect so they are sent to the relay.
+fn announced_port(settings: &Settings, config: &SimConnectConfigResult) -> String {
+    if settings.is_sim_behind_relay() {
+        settings.relay.port.to_string()
+    } else {
+        config.port.clone()
+    }
+}
+
+fn connection_string(
+    settings: &Settings,
+    interface: &ExposedInterface,
+    config: &SimConnectConfigResult,
+) -> String {
+    format!(
+        "{}:{}",
+        reachable_address(interface),
+        announced_port(settings, config)
+    )
+}
+
+fn start_broadcasting(
+    proxy: &EventLoopProxy<AppEvent>,
+    settings: &Settings,
+    interface: ExposedInterface,
+    config: &SimConnectConfigResult,
+    stats: &Arc<Mutex<BroadcastStats>>,
+    is_withdrawn: bool,
+) -> mpsc::Sender<BroadcastCommand> {
+    let (command_sender, command_receiver) = mpsc::channel();
+
+    let proxy = proxy.clone();
+
+    let broadcast_config = BroadcastConfig {
+        simconnect_port: announced_port(settings, config),
+        interface,
+        ipv6: config.ipv6.clone(),
+        ipv6_scope_ids: ipv6_scope_ids(&settings.exposure).unwrap_or_default(),
+        unicast: settings.unicast.clone(),
+        is_withdrawn,
+        stats: stats.clone(),
+    };
+
+    thread::spawn(move || udp_broadcast_thread(proxy, command_receiver, broadcast_config));
+
+    command_sender
+}
+
+/// Runs a one-shot CLI command and returns the process exit code.
+fn run_command(command: Command, settings: &Settings) -> i32 {
+    match command {
+        Command::Run => 0,
+        Command::Help => {
+            println!("{}", usage());
+            0
+        }
+        Command::Reflector => match run_reflector(&settings.reflector) {
+            Ok(_) => 0,
+            Err(error) => {
+                log::error!("{}", error);
+                eprintln!("{}", error);
+                1
+            }
+        },
+        Command::Autostart { enabled: None } => {
+            println!(
+                "Autostart is {}",
+                if is_autostart_enabled() { "on" } else { "off" }
+            );
+            0
+        }
+        Command::Autostart {
+            enabled: Some(enabled),
+        } => match set_autostart(enabled) {
+            Ok(_) => {
+                println!("Autostart turned {}", if enabled { "on" } else { "off" });
+                0
+            }
+            Err(error) => {
+                eprintln!("{}", error);
+                1
+            }
+        },
+        Command::Diagnostics { output } => {
+            let output = output.unwrap_or_else(get_default_diagnostics_path);
+
+            match export_diagnostics(&output, settings, None) {
+                Ok(_) => {
+                    println!("Diagnostics bundle written to {}", output.display());
+                    0
+                }
+                Err(error) => {
+                    eprintln!("{}", error);
+                    1
+                }
+            }
+        }
+    }
+}
+
+/// Hands the command to the instance holding the lock, returns the exit code.
+fn forward_command(command: Command) -> i32 {
+    let request = match command {
+        Command::Diagnostics { output } => {
+            let output = output.unwrap_or_else(get_default_diagnostics_path);
+
+            InstanceRequest::Diagnostics {
+                output: std::path::absolute(&output).unwrap_or(output),
+            }
+        }
+        _ => InstanceRequest::Show,
+    };
+
+    log::info!("Another instance is running, forwarding {:?}", request);
+
+    match send_to_running_instance(request) {
+        Ok(reply) => {
+            println!("{}", reply);
+            0
+        }
+        Err(error) => {
+            log::error!("{}", error);
+            eprintln!("{}", error);
+            1
+        }
+    }
+}
+
 fn main() {
-    env_logger::init();
+    let settings = Settings::load();
+    init_logger(
+        settings
+            .as_ref()
+            .map(Settings::log_level_filter)
+            .unwrap_or(log::LevelFilter::Info),
+    );
+    init_metrics();
+    log::info!("Starting {} {}", APP_TITLE, RELESE_TAG);
+
+    let settings = settings.unwrap_or_else(|error| {
+        log::error!("{}, using default settings", error);
+        Settings::default()
+    });
+
+    let command = match parse_args(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(error) => {
+            attach_parent_console();
+            eprintln!("{}\n\n{}", error, usage());
+            std::process::exit(2);
+        }
+    };
+
+    /* Only one instance rewrites SimConnect.xml and broadcasts, later launches hand over to it */
+    let instance_lock = match command {
+        Command::Run | Command::Diagnostics { .. } => match acquire_instance_lock() {
+            Ok(Some(instance_lock)) => Some(instance_lock),
+            Ok(None) => {
+                attach_parent_console();
+                std::process::exit(forward_command(command));
+            }
+            Err(error) => {
+                log::warn!("{}, skipping the single-instance check", error);
+                None
+            }
+        },
+        _ => None,
+    };
+
+    if !matches!(command, Command::Run) {
+        drop(instance_lock);
+        attach_parent_console();
+        std::process::exit(run_command(command, &settings));
+    }
 
     let mut app_state = AppState::new();
 
-    let event_loop = EventLoopBuilder::new().build().unwrap();
+    let event_loop = EventLoopBuilder::<AppEvent>::with_user_event()
+        .build()
+        .unwrap();
+
+    if let Some(instance_lock) = &instance_lock {
+        if let Err(error) = instance_lock.listen(event_loop.create_proxy()) {
+            log::error!("{}", error);
+        }
+    }
 
     let window = WindowBuilder::new()
         .with_title(APP_TITLE)
         .with_theme(Some(Theme::Dark))
         .with_active(false)
-        .with_resizable(false)
+        .with_resizable(true)
         .with_visible(false)
-        .with_inner_size(PhysicalSize {
-            width: 600,
-            height: 240,
+        .with_inner_size(LogicalSize {
+            width: 720,
+            height: 480,
+        })
+        .with_min_inner_size(LogicalSize {
+            width: 640,
+            height: 300,
         })
-        .with_position(PhysicalPosition { x: 200, y: 200 })
-        .with_enabled_buttons(WindowButtons::MINIMIZE.union(WindowButtons::CLOSE))
+        .with_position(LogicalPosition { x: 200, y: 200 })
+        .with_enabled_buttons(
+            WindowButtons::MINIMIZE
+                .union(WindowButtons::MAXIMIZE)
+                .union(WindowButtons::CLOSE),
+        )
         .with_window_icon(Some(get_window_icon()))
         .build(&event_loop)
         .unwrap();
 
-    pollster::block_on(run(&window, &mut app_state, event_loop));
+    pollster::block_on(run(&window, &mut app_state, settings, event_loop));
 }
@@ -0,0 +1,350 @@
+use log::LevelFilter;
+use serde_derive::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+use crate::APP_TITLE;
+
+static SETTINGS_FILE_NAME: &str = "settings.json";
+
+/// Which local network SimConnect is exposed to and announced on.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "mode", content = "value", rename_all = "snake_case")]
+pub enum Exposure {
+    /// Listen on every interface (`0.0.0.0`) and broadcast globally.
+    #[default]
+    All,
+    /// A single interface, matched by its name or by its IPv4 address.
+    Interface(String),
+    /// The first interface whose IPv4 address falls into the CIDR, e.g. `192.168.1.0/24`.
+    Subnet(String),
+}
+
+/// How the client starts the simulator once SimConnect.xml is written.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "mode", content = "value", rename_all = "snake_case")]
+pub enum SimLauncher {
+    /// The simulator is started by the user.
+    #[default]
+    Off,
+    /// Steam app ID, `1250410` for MSFS 2020 and `2537590` for MSFS 2024.
+    Steam(u32),
+    /// Store application ID, e.g. `Microsoft.FlightSimulator_8wekyb3d8bbwe!App`.
+    Store(String),
+    /// Command line run through the shell.
+    Command(String),
+}
+
+/// Launching the simulator after the configuration, so it always loads the new SimConnect.xml.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LaunchSettings {
+    pub launcher: SimLauncher,
+    /// How long to wait for the SimConnect pipe before giving up.
+    pub timeout_secs: u64,
+}
+
+impl Default for LaunchSettings {
+    fn default() -> Self {
+        LaunchSettings {
+            launcher: SimLauncher::Off,
+            timeout_secs: 600,
+        }
+    }
+}
+
+/// Optional IPv6 `SimConnect.Comm` section managed next to the IPv4 one.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Ipv6Settings {
+    pub enabled: bool,
+    pub address: String,
+    pub port: String,
+}
+
+impl Default for Ipv6Settings {
+    fn default() -> Self {
+        Ipv6Settings {
+            enabled: false,
+            address: "::".to_string(),
+            port: "501".to_string(),
+        }
+    }
+}
+
+/// Preflight of the SimConnect port before it is written to SimConnect.xml.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PortSettings {
+    /// Pick the next free port from the range when the configured one is taken.
+    pub auto_select: bool,
+    pub range_start: u16,
+    pub range_end: u16,
+}
+
+impl Default for PortSettings {
+    fn default() -> Self {
+        PortSettings {
+            auto_select: false,
+            range_start: 500,
+            range_end: 520,
+        }
+    }
+}
+
+/// Periodic check that SimConnect accepts connections on the announced endpoint.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ProbeSettings {
+    pub enabled: bool,
+    pub interval_secs: u64,
+    pub timeout_ms: u64,
+    /// Also perform the SimConnect Open handshake after connecting.
+    pub handshake: bool,
+}
+
+impl Default for ProbeSettings {
+    fn default() -> Self {
+        ProbeSettings {
+            enabled: true,
+            interval_secs: 15,
+            timeout_ms: 2000,
+            handshake: false,
+        }
+    }
+}
+
+/// Embedded HTTP server exposing the client's state as JSON.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ApiSettings {
+    pub enabled: bool,
+    /// `127.0.0.1` keeps the API local, `0.0.0.0` exposes it to the LAN.
+    pub bind: String,
+    pub port: u16,
+    /// Expected as `Authorization: Bearer <token>`, required when not bound to loopback.
+    pub token: Option<String>,
+}
+
+impl Default for ApiSettings {
+    fn default() -> Self {
+        ApiSettings {
+            enabled: false,
+            bind: "127.0.0.1".to_string(),
+            port: 8750,
+            token: None,
+        }
+    }
+}
+
+/// Prometheus scrape endpoint, served separately from the JSON API.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MetricsSettings {
+    pub enabled: bool,
+    pub bind: String,
+    pub port: u16,
+}
+
+impl Default for MetricsSettings {
+    fn default() -> Self {
+        MetricsSettings {
+            enabled: false,
+            bind: "127.0.0.1".to_string(),
+            port: 9750,
+        }
+    }
+}
+
+/// TCP relay for devices that can't reach the SimConnect endpoint directly.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RelaySettings {
+    pub enabled: bool,
+    pub bind: String,
+    pub port: u16,
+    /// IPv4 CIDRs or single addresses allowed to connect, empty allows everyone.
+    pub allow: Vec<String>,
+    /// Keep SimConnect on `127.0.0.1` and announce the relay port instead.
+    pub loopback_sim: bool,
+}
+
+impl Default for RelaySettings {
+    fn default() -> Self {
+        RelaySettings {
+            enabled: false,
+            bind: "0.0.0.0".to_string(),
+            port: 5500,
+            allow: Vec::new(),
+            loopback_sim: true,
+        }
+    }
+}
+
+/// Tracking of remote devices connected to SimConnect.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ClientSettings {
+    pub enabled: bool,
+    pub poll_secs: u64,
+}
+
+impl Default for ClientSettings {
+    fn default() -> Self {
+        ClientSettings {
+            enabled: true,
+            poll_secs: 2,
+        }
+    }
+}
+
+/// Desktop notifications, one switch per category.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct NotificationSettings {
+    /// SimConnect.xml changed while the simulator was running.
+    pub config_changed: bool,
+    /// Broadcasting failed or recovered.
+    pub broadcaster: bool,
+    /// The simulator started or stopped.
+    pub sim_presence: bool,
+    /// A new device connected to SimConnect.
+    pub new_client: bool,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        NotificationSettings {
+            config_changed: true,
+            broadcaster: true,
+            sim_presence: false,
+            new_client: false,
+        }
+    }
+}
+
+/// Hosts that get the announcement directly, for networks that drop broadcast.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct UnicastSettings {
+    /// `host` or `host:port`, the port defaults to the broadcast port.
+    pub targets: Vec<String>,
+    /// How often (in seconds) host names are resolved again.
+    pub resolve_interval_secs: u64,
+}
+
+impl Default for UnicastSettings {
+    fn default() -> Self {
+        UnicastSettings {
+            targets: Vec::new(),
+            resolve_interval_secs: 300,
+        }
+    }
+}
+
+/// Interfaces used by the `reflector` command.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ReflectorSettings {
+    /// Interface names or IPv4 addresses, all non-loopback interfaces when empty.
+    pub interfaces: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    pub exposure: Exposure,
+    /// How often (in seconds) the exposed interface is checked for a new address.
+    pub interface_poll_secs: u64,
+    pub ipv6: Ipv6Settings,
+    pub port: PortSettings,
+    pub probe: ProbeSettings,
+    pub launch: LaunchSettings,
+    /// One of `error`, `warn`, `info`, `debug`, `trace`.
+    pub log_level: String,
+    pub api: ApiSettings,
+    pub metrics: MetricsSettings,
+    pub relay: RelaySettings,
+    pub clients: ClientSettings,
+    pub notifications: NotificationSettings,
+    pub reflector: ReflectorSettings,
+    pub unicast: UnicastSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            exposure: Exposure::All,
+            interface_poll_secs: 5,
+            ipv6: Ipv6Settings::default(),
+            port: PortSettings::default(),
+            probe: ProbeSettings::default(),
+            launch: LaunchSettings::default(),
+            log_level: "info".to_string(),
+            api: ApiSettings::default(),
+            metrics: MetricsSettings::default(),
+            relay: RelaySettings::default(),
+            clients: ClientSettings::default(),
+            notifications: NotificationSettings::default(),
+            reflector: ReflectorSettings::default(),
+            unicast: UnicastSettings::default(),
+        }
+    }
+}
+
+pub fn get_app_data_dir() -> Result<PathBuf, String> {
+    dirs::config_dir()
+        .map(|dir| dir.join(APP_TITLE))
+        .ok_or_else(|| "Unable to determine user config directory.".to_string())
+}
+
+pub fn get_app_log_dir() -> Result<PathBuf, String> {
+    dirs::data_local_dir()
+        .map(|dir| dir.join(APP_TITLE).join("logs"))
+        .ok_or_else(|| "Unable to determine user data directory.".to_string())
+}
+
+pub fn get_settings_path() -> Result<PathBuf, String> {
+    Ok(get_app_data_dir()?.join(SETTINGS_FILE_NAME))
+}
+
+impl Settings {
+    /// Whether SimConnect is kept on loopback and only reachable through the relay.
+    pub fn is_sim_behind_relay(&self) -> bool {
+        self.relay.enabled && self.relay.loopback_sim
+    }
+
+    pub fn log_level_filter(&self) -> LevelFilter {
+        self.log_level.parse().unwrap_or(LevelFilter::Info)
+    }
+
+    /// Loads settings from the user's config directory.
+    /// A missing file is created with the defaults so it can be edited by hand.
+    pub fn load() -> Result<Self, String> {
+        let settings_path = get_settings_path()?;
+
+        if !settings_path.exists() {
+            let settings = Settings::default();
+            settings.save()?;
+            return Ok(settings);
+        }
+
+        let content = fs::read_to_string(&settings_path)
+            .map_err(|e| format!("Error reading settings: {}", e))?;
+
+        serde_json::from_str(&content).map_err(|e| format!("Error parsing settings: {}", e))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let settings_path = get_settings_path()?;
+
+        if let Some(parent) = settings_path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Error creating settings directory: {}", e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Error serializing settings: {}", e))?;
+
+        fs::write(&settings_path, content).map_err(|e| format!("Error writing settings: {}", e))
+    }
+}
@@ -1,4 +1,16 @@
-#[derive(PartialEq)]
+use chrono::{DateTime, Local};
+use serde_derive::Serialize;
+use std::{collections::VecDeque, net::IpAddr, path::PathBuf, sync::mpsc, time::SystemTime};
+
+use crate::utils::{
+    instance::InstanceRequest, net::ExposedInterface, port::PortDecision, probe::ProbeResult,
+};
+
+/// Number of state events kept in the history.
+static STATE_HISTORY_LIMIT: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum AppStatus {
     Neutral,
     Running,
@@ -6,16 +18,795 @@ pub enum AppStatus {
     Error,
 }
 
-pub struct AppState {
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartReason {
+    /// SimConnect.xml was changed after the simulator had already started.
+    StartOrder,
+    /// The exposed interface got a new address while the simulator was running.
+    AddressChanged,
+    /// The configuration was re-applied from the tray while the simulator was running.
+    ConfigReapplied,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigPhase {
+    Pending,
+    Applied,
+    /// The configuration is written, but the simulator has to be restarted to load it.
+    RestartRequired(RestartReason),
+    PortConflict,
+    Failed,
+    /// The original SimConnect.xml was put back, nothing is announced until it is applied again.
+    Restored,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BroadcasterState {
+    Idle,
+    Running,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SimPresence {
+    Unknown,
+    Running,
+    NotRunning,
+}
+
+impl SimPresence {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SimPresence::Unknown => "Unknown",
+            SimPresence::Running => "Running",
+            SimPresence::NotRunning => "Not running",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointHealth {
+    Unknown,
+    Listening,
+    NotAccepting,
+}
+
+impl EndpointHealth {
+    pub fn label(&self) -> &'static str {
+        match self {
+            EndpointHealth::Unknown => "",
+            EndpointHealth::Listening => "Sim listening",
+            EndpointHealth::NotAccepting => "Sim not accepting connections",
+        }
+    }
+}
+
+impl From<&ProbeResult> for EndpointHealth {
+    fn from(result: &ProbeResult) -> Self {
+        match result {
+            ProbeResult::Listening {
+                handshake: Some(false),
+                ..
+            } => EndpointHealth::NotAccepting,
+            ProbeResult::Listening { .. } => EndpointHealth::Listening,
+            ProbeResult::NotAccepting => EndpointHealth::NotAccepting,
+        }
+    }
+}
+
+/// Progress of a simulator started by the client.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "phase", content = "error", rename_all = "snake_case")]
+pub enum SimLaunchPhase {
+    /// The launcher was run, waiting for the SimConnect pipe.
+    Launching,
+    Started,
+    TimedOut,
+    Failed(String),
+}
+
+/// Inputs of the state machine, serialized as `{"type": ..., "data": ...}` for API clients.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum StateEvent {
+    ConfigApplied {
+        address: String,
+        port: String,
+        /// `IP:port` remote devices connect to, differs from the above behind the relay.
+        connection_string: String,
+        port_decision: PortDecision,
+        restart_required: Option<RestartReason>,
+    },
+    ConfigFailed(String),
+    ConfigRestored,
+    AnnouncementsPaused(bool),
+    BroadcasterChanged(BroadcasterState),
+    SimPresenceChanged(bool),
+    SimLaunchChanged(SimLaunchPhase),
+    EndpointHealthChanged(EndpointHealth),
+    /// Remote devices that just connected to SimConnect.
+    ClientsConnected(Vec<IpAddr>),
+}
+
+impl StateEvent {
+    pub fn describe(&self) -> String {
+        match self {
+            StateEvent::ConfigApplied {
+                address,
+                port,
+                restart_required,
+                ..
+            } => format!(
+                "SimConnect configured on {}:{}{}",
+                address,
+                port,
+                if restart_required.is_some() {
+                    " (simulator restart required)"
+                } else {
+                    ""
+                }
+            ),
+            StateEvent::ConfigFailed(error) => format!("Configuration failed: {}", error),
+            StateEvent::ConfigRestored => "Original SimConnect.xml restored".to_string(),
+            StateEvent::AnnouncementsPaused(true) => "Announcements paused".to_string(),
+            StateEvent::AnnouncementsPaused(false) => "Announcements resumed".to_string(),
+            StateEvent::BroadcasterChanged(BroadcasterState::Idle) => {
+                "Broadcasting stopped".to_string()
+            }
+            StateEvent::BroadcasterChanged(BroadcasterState::Running) => {
+                "Broadcasting started".to_string()
+            }
+            StateEvent::BroadcasterChanged(BroadcasterState::Failed) => {
+                "Broadcasting failed".to_string()
+            }
+            StateEvent::SimPresenceChanged(true) => "Simulator started".to_string(),
+            StateEvent::SimPresenceChanged(false) => "Simulator stopped".to_string(),
+            StateEvent::SimLaunchChanged(SimLaunchPhase::Launching) => {
+                "Launching the simulator".to_string()
+            }
+            StateEvent::SimLaunchChanged(SimLaunchPhase::Started) => {
+                "Simulator launched".to_string()
+            }
+            StateEvent::SimLaunchChanged(SimLaunchPhase::TimedOut) => {
+                "Simulator didn't start in time".to_string()
+            }
+            StateEvent::SimLaunchChanged(SimLaunchPhase::Failed(error)) => {
+                format!("Simulator launch failed: {}", error)
+            }
+            StateEvent::EndpointHealthChanged(EndpointHealth::Unknown) => {
+                "Endpoint state unknown".to_string()
+            }
+            StateEvent::EndpointHealthChanged(endpoint_health) => {
+                endpoint_health.label().to_string()
+            }
+            StateEvent::ClientsConnected(addresses) => format!(
+                "Device connected: {}",
+                addresses
+                    .iter()
+                    .map(IpAddr::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StateRecord {
+    pub at: SystemTime,
+    pub event: StateEvent,
+}
+
+impl StateRecord {
+    pub fn describe(&self) -> String {
+        format!(
+            "{} {}",
+            DateTime::<Local>::from(self.at).format("%Y-%m-%d %H:%M:%S"),
+            self.event.describe()
+        )
+    }
+}
+
+/// Copy of the state shared with the API threads.
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusSnapshot {
     pub status: AppStatus,
-    pub msg_text: String,
+    pub message: String,
+    pub detail: String,
+    pub simconnect_address: Option<String>,
+    pub simconnect_port: Option<String>,
+    pub connection_string: Option<String>,
+    pub sim_presence: SimPresence,
+    pub sim_launch: Option<SimLaunchPhase>,
+    pub endpoint_health: EndpointHealth,
+    pub broadcaster: BroadcasterState,
+    pub is_paused: bool,
+}
+
+pub struct AppState {
+    pub config_phase: ConfigPhase,
+    pub broadcaster: BroadcasterState,
+    pub sim_presence: SimPresence,
+    /// `None` unless the client launches the simulator.
+    pub sim_launch: Option<SimLaunchPhase>,
+    pub endpoint_health: EndpointHealth,
+    pub simconnect_address: Option<String>,
+    pub simconnect_port: Option<String>,
+    pub connection_string: Option<String>,
+    pub port_decision: Option<PortDecision>,
+    pub config_error: Option<String>,
+    pub is_paused: bool,
+    /// The simulator stopped after having been running, so its endpoint was withdrawn.
+    pub is_sim_withdrawn: bool,
+    history: VecDeque<StateRecord>,
+    observers: Vec<mpsc::Sender<StateRecord>>,
 }
 
 impl AppState {
     pub fn new() -> Self {
         AppState {
-            status: AppStatus::Neutral,
-            msg_text: "Checking...".to_string(),
+            config_phase: ConfigPhase::Pending,
+            broadcaster: BroadcasterState::Idle,
+            sim_presence: SimPresence::Unknown,
+            sim_launch: None,
+            endpoint_health: EndpointHealth::Unknown,
+            simconnect_address: None,
+            simconnect_port: None,
+            connection_string: None,
+            port_decision: None,
+            config_error: None,
+            is_paused: false,
+            is_sim_withdrawn: false,
+            history: VecDeque::with_capacity(STATE_HISTORY_LIMIT),
+            observers: Vec::new(),
+        }
+    }
+
+    /// Applies the event and returns whether the state changed.
+    /// Every change is recorded in the history and sent to the observers.
+    pub fn apply(&mut self, event: StateEvent) -> bool {
+        let is_changed = match &event {
+            StateEvent::ConfigApplied {
+                address,
+                port,
+                connection_string,
+                port_decision,
+                restart_required,
+            } => {
+                self.simconnect_address = Some(address.clone());
+                self.simconnect_port = Some(port.clone());
+                self.connection_string = Some(connection_string.clone());
+                self.port_decision = Some(port_decision.clone());
+                self.config_error = None;
+
+                self.config_phase = match (restart_required, port_decision) {
+                    (Some(reason), _) => ConfigPhase::RestartRequired(*reason),
+                    (None, PortDecision::Conflict { .. }) => ConfigPhase::PortConflict,
+                    (None, _) => ConfigPhase::Applied,
+                };
+                true
+            }
+            StateEvent::ConfigFailed(error) => {
+                self.config_phase = ConfigPhase::Failed;
+                self.config_error = Some(error.clone());
+                true
+            }
+            StateEvent::ConfigRestored => {
+                self.config_phase = ConfigPhase::Restored;
+                self.connection_string = None;
+                true
+            }
+            StateEvent::AnnouncementsPaused(is_paused) => {
+                let is_changed = self.is_paused != *is_paused;
+                self.is_paused = *is_paused;
+                is_changed
+            }
+            StateEvent::BroadcasterChanged(broadcaster) => {
+                let is_changed = self.broadcaster != *broadcaster;
+                self.broadcaster = *broadcaster;
+                is_changed
+            }
+            StateEvent::SimPresenceChanged(is_running) => {
+                let sim_presence = if *is_running {
+                    SimPresence::Running
+                } else {
+                    SimPresence::NotRunning
+                };
+
+                /* A simulator started after the rewrite has loaded the new configuration */
+                if sim_presence == SimPresence::Running
+                    && self.sim_presence == SimPresence::NotRunning
+                {
+                    if let ConfigPhase::RestartRequired(_) = self.config_phase {
+                        self.config_phase = ConfigPhase::Applied;
+                    }
+                }
+
+                /* Only a simulator that stops while announced is withdrawn, the client usually starts first */
+                self.is_sim_withdrawn = match sim_presence {
+                    SimPresence::Running => false,
+                    _ => self.is_sim_withdrawn || self.sim_presence == SimPresence::Running,
+                };
+
+                let is_changed = self.sim_presence != sim_presence;
+                self.sim_presence = sim_presence;
+                is_changed
+            }
+            StateEvent::SimLaunchChanged(sim_launch) => {
+                let is_changed = self.sim_launch.as_ref() != Some(sim_launch);
+                self.sim_launch = Some(sim_launch.clone());
+                is_changed
+            }
+            StateEvent::EndpointHealthChanged(endpoint_health) => {
+                let is_changed = self.endpoint_health != *endpoint_health;
+                self.endpoint_health = *endpoint_health;
+                is_changed
+            }
+            /* Nothing to keep, recorded for the history and the notifications */
+            StateEvent::ClientsConnected(addresses) => !addresses.is_empty(),
+        };
+
+        if is_changed {
+            self.record(event);
+        }
+
+        is_changed
+    }
+
+    fn record(&mut self, event: StateEvent) {
+        log::info!("State changed: {:?}", event);
+
+        let record = StateRecord {
+            at: SystemTime::now(),
+            event,
+        };
+
+        self.observers
+            .retain(|observer| observer.send(record.clone()).is_ok());
+
+        if self.history.len() == STATE_HISTORY_LIMIT {
+            self.history.pop_front();
+        }
+        self.history.push_back(record);
+    }
+
+    /// Returns a receiver of every future state change.
+    pub fn subscribe(&mut self) -> mpsc::Receiver<StateRecord> {
+        let (sender, receiver) = mpsc::channel();
+        self.observers.push(sender);
+        receiver
+    }
+
+    /// Recorded state changes, oldest first.
+    pub fn history(&self) -> impl DoubleEndedIterator<Item = &StateRecord> {
+        self.history.iter()
+    }
+
+    /// Broadcasting only makes sense once the simulator will pick up the configuration.
+    pub fn can_broadcast(&self) -> bool {
+        self.config_phase == ConfigPhase::Applied
+    }
+
+    /// Announcements stay off while paused or after the simulator's endpoint was withdrawn.
+    pub fn is_withdrawn(&self) -> bool {
+        self.is_paused || self.is_sim_withdrawn
+    }
+
+    pub fn status(&self) -> AppStatus {
+        match (self.config_phase, self.broadcaster) {
+            (ConfigPhase::Failed, _) | (_, BroadcasterState::Failed) => AppStatus::Error,
+            (ConfigPhase::RestartRequired(_), _) | (ConfigPhase::PortConflict, _) => {
+                AppStatus::Warning
+            }
+            _ if self.has_sim_launch_failed() => AppStatus::Warning,
+            _ if self.is_paused => AppStatus::Neutral,
+            (_, BroadcasterState::Running) => AppStatus::Running,
+            _ => AppStatus::Neutral,
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match (self.config_phase, self.broadcaster) {
+            (ConfigPhase::Failed, _) => "🔴 Fatal error during SimConnect configuration.",
+            (_, BroadcasterState::Failed) => "🔴 Fatal error during data broadcasting.",
+            (ConfigPhase::RestartRequired(RestartReason::StartOrder), _) => {
+                "⭕ Run this client before the simulator is started."
+            }
+            (ConfigPhase::RestartRequired(RestartReason::AddressChanged), _) => {
+                "⭕ Network address changed, restart the simulator."
+            }
+            (ConfigPhase::RestartRequired(RestartReason::ConfigReapplied), _) => {
+                "⭕ SimConnect.xml re-applied, restart the simulator."
+            }
+            _ if self.has_sim_launch_failed() => "⭕ The simulator didn't start.",
+            _ if self.sim_launch == Some(SimLaunchPhase::Launching) => {
+                "🚀 Starting the simulator..."
+            }
+            (ConfigPhase::Restored, _) => "Original SimConnect.xml restored.",
+            _ if self.is_paused => "⏸ Announcements are paused.",
+            (ConfigPhase::PortConflict, _) => "⭕ SimConnect port is used by another application.",
+            (_, BroadcasterState::Running) => "✅ Client is working normally.",
+            _ => "Checking...",
+        }
+    }
+
+    pub fn snapshot(&self) -> StatusSnapshot {
+        StatusSnapshot {
+            status: self.status(),
+            message: self.message().to_string(),
+            detail: self.detail(),
+            simconnect_address: self.simconnect_address.clone(),
+            simconnect_port: self.simconnect_port.clone(),
+            connection_string: self.connection_string.clone(),
+            sim_presence: self.sim_presence,
+            sim_launch: self.sim_launch.clone(),
+            endpoint_health: self.endpoint_health,
+            broadcaster: self.broadcaster,
+            is_paused: self.is_paused,
+        }
+    }
+
+    fn has_sim_launch_failed(&self) -> bool {
+        matches!(
+            self.sim_launch,
+            Some(SimLaunchPhase::TimedOut | SimLaunchPhase::Failed(_))
+        )
+    }
+
+    /// Secondary line under the message, e.g. the SimConnect port decision.
+    pub fn detail(&self) -> String {
+        match &self.sim_launch {
+            Some(SimLaunchPhase::Failed(error)) => return error.clone(),
+            Some(SimLaunchPhase::TimedOut) => {
+                return "The SimConnect pipe didn't appear, start the simulator manually."
+                    .to_string()
+            }
+            _ => {}
+        }
+
+        match (&self.port_decision, &self.simconnect_port) {
+            (Some(PortDecision::Free), _) | (None, _) | (_, None) => String::new(),
+            (Some(port_decision), Some(port)) => port_decision.describe(port),
+        }
+    }
+}
+
+/// Events sent from background threads to the UI event loop.
+#[derive(Debug)]
+pub enum AppEvent {
+    BroadcastOk,
+    /// Sent after every successful announcement.
+    AnnouncementSent,
+    /// Nothing of an announcement got out, the broadcaster keeps trying.
+    AnnouncementFailed,
+    /// The broadcaster stopped, e.g. its socket couldn't be opened.
+    BroadcastError,
+    /// The delay before starting a stopped broadcaster again has passed.
+    RetryBroadcast,
+    ExposedInterfaceChanged(ExposedInterface),
+    EndpointProbed(ProbeResult),
+    SimPresenceChanged(bool),
+    RestartBroadcast,
+    /// The connected clients changed, carries the addresses that just connected.
+    ClientsChanged(Vec<IpAddr>),
+    /// Resolve the exposure and write SimConnect.xml again.
+    ReapplyConfig,
+    SimLaunchChanged(SimLaunchPhase),
+    /// Sent by a later launch, the result is its answer.
+    InstanceRequest(InstanceRequest, mpsc::Sender<Result<String, String>>),
+    /// The diagnostics bundle was written, or not. Requests from a later launch carry its reply sender.
+    DiagnosticsExported {
+        output: PathBuf,
+        result: Result<(), String>,
+        reply_sender: Option<mpsc::Sender<Result<String, String>>>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_applied(
+        port_decision: PortDecision,
+        restart_required: Option<RestartReason>,
+    ) -> StateEvent {
+        StateEvent::ConfigApplied {
+            address: "192.168.1.10".to_string(),
+            port: "500".to_string(),
+            connection_string: "192.168.1.10:500".to_string(),
+            port_decision,
+            restart_required,
         }
     }
+
+    /// Configured, broadcasting and the simulator running.
+    fn running_state() -> AppState {
+        let mut app_state = AppState::new();
+        app_state.apply(config_applied(PortDecision::Free, None));
+        app_state.apply(StateEvent::BroadcasterChanged(BroadcasterState::Running));
+        app_state.apply(StateEvent::SimPresenceChanged(true));
+        app_state
+    }
+
+    #[test]
+    fn starts_neutral() {
+        let app_state = AppState::new();
+
+        assert_eq!(app_state.status(), AppStatus::Neutral);
+        assert_eq!(app_state.message(), "Checking...");
+        assert!(!app_state.can_broadcast());
+        assert_eq!(app_state.history().count(), 0);
+    }
+
+    #[test]
+    fn config_applied_allows_broadcasting() {
+        let mut app_state = AppState::new();
+
+        assert!(app_state.apply(config_applied(PortDecision::Free, None)));
+        assert_eq!(app_state.config_phase, ConfigPhase::Applied);
+        assert!(app_state.can_broadcast());
+        assert_eq!(
+            app_state.connection_string.as_deref(),
+            Some("192.168.1.10:500")
+        );
+        assert_eq!(app_state.status(), AppStatus::Neutral);
+        assert_eq!(app_state.detail(), "");
+    }
+
+    #[test]
+    fn config_applied_with_restart_required_holds_broadcasting_back() {
+        let mut app_state = AppState::new();
+
+        assert!(app_state.apply(config_applied(
+            PortDecision::Free,
+            Some(RestartReason::StartOrder)
+        )));
+        assert_eq!(
+            app_state.config_phase,
+            ConfigPhase::RestartRequired(RestartReason::StartOrder)
+        );
+        assert!(!app_state.can_broadcast());
+        assert_eq!(app_state.status(), AppStatus::Warning);
+        assert_eq!(
+            app_state.message(),
+            "⭕ Run this client before the simulator is started."
+        );
+    }
+
+    #[test]
+    fn restart_required_is_cleared_by_a_simulator_start() {
+        let mut app_state = AppState::new();
+        app_state.apply(StateEvent::SimPresenceChanged(false));
+        app_state.apply(config_applied(
+            PortDecision::Free,
+            Some(RestartReason::AddressChanged),
+        ));
+
+        assert_eq!(
+            app_state.message(),
+            "⭕ Network address changed, restart the simulator."
+        );
+        assert!(app_state.apply(StateEvent::SimPresenceChanged(true)));
+        assert_eq!(app_state.config_phase, ConfigPhase::Applied);
+        assert!(app_state.can_broadcast());
+    }
+
+    #[test]
+    fn config_applied_with_port_conflict_warns() {
+        let mut app_state = AppState::new();
+
+        app_state.apply(config_applied(
+            PortDecision::Conflict {
+                owner_pid: Some(42),
+            },
+            None,
+        ));
+
+        assert_eq!(app_state.config_phase, ConfigPhase::PortConflict);
+        assert!(!app_state.can_broadcast());
+        assert_eq!(app_state.status(), AppStatus::Warning);
+        assert_eq!(
+            app_state.detail(),
+            "Port 500 is used by another process (PID 42)."
+        );
+    }
+
+    #[test]
+    fn config_failed_is_an_error() {
+        let mut app_state = running_state();
+
+        assert!(app_state.apply(StateEvent::ConfigFailed("No SimConnect.xml".to_string())));
+        assert_eq!(app_state.status(), AppStatus::Error);
+        assert_eq!(
+            app_state.message(),
+            "🔴 Fatal error during SimConnect configuration."
+        );
+        assert_eq!(app_state.config_error.as_deref(), Some("No SimConnect.xml"));
+        assert!(!app_state.can_broadcast());
+
+        assert!(app_state.apply(config_applied(PortDecision::Free, None)));
+        assert_eq!(app_state.config_error, None);
+        assert_eq!(app_state.status(), AppStatus::Running);
+    }
+
+    #[test]
+    fn broadcaster_changes() {
+        let mut app_state = AppState::new();
+        app_state.apply(config_applied(PortDecision::Free, None));
+
+        assert!(app_state.apply(StateEvent::BroadcasterChanged(BroadcasterState::Running)));
+        assert_eq!(app_state.status(), AppStatus::Running);
+        assert_eq!(app_state.message(), "✅ Client is working normally.");
+
+        assert!(!app_state.apply(StateEvent::BroadcasterChanged(BroadcasterState::Running)));
+
+        assert!(app_state.apply(StateEvent::BroadcasterChanged(BroadcasterState::Failed)));
+        assert_eq!(app_state.status(), AppStatus::Error);
+        assert_eq!(
+            app_state.message(),
+            "🔴 Fatal error during data broadcasting."
+        );
+        /* A failed broadcaster doesn't invalidate the configuration */
+        assert!(app_state.can_broadcast());
+
+        assert!(app_state.apply(StateEvent::BroadcasterChanged(BroadcasterState::Idle)));
+        assert_eq!(app_state.status(), AppStatus::Neutral);
+    }
+
+    #[test]
+    fn sim_presence_changes() {
+        let mut app_state = AppState::new();
+
+        assert!(app_state.apply(StateEvent::SimPresenceChanged(true)));
+        assert_eq!(app_state.sim_presence, SimPresence::Running);
+        assert!(!app_state.apply(StateEvent::SimPresenceChanged(true)));
+
+        assert!(app_state.apply(StateEvent::SimPresenceChanged(false)));
+        assert_eq!(app_state.sim_presence, SimPresence::NotRunning);
+        assert_eq!(app_state.status(), AppStatus::Neutral);
+    }
+
+    #[test]
+    fn stopped_simulator_withdraws_until_it_runs_again() {
+        let mut app_state = AppState::new();
+
+        app_state.apply(StateEvent::SimPresenceChanged(false));
+        assert!(!app_state.is_withdrawn());
+
+        app_state.apply(StateEvent::SimPresenceChanged(true));
+        assert!(!app_state.is_withdrawn());
+
+        app_state.apply(StateEvent::SimPresenceChanged(false));
+        assert!(app_state.is_sim_withdrawn);
+        assert!(app_state.is_withdrawn());
+
+        /* Unpausing doesn't announce a stopped simulator */
+        app_state.apply(StateEvent::AnnouncementsPaused(true));
+        app_state.apply(StateEvent::AnnouncementsPaused(false));
+        assert!(app_state.is_withdrawn());
+
+        app_state.apply(StateEvent::SimPresenceChanged(true));
+        assert!(!app_state.is_withdrawn());
+
+        app_state.apply(StateEvent::AnnouncementsPaused(true));
+        assert!(app_state.is_withdrawn());
+    }
+
+    #[test]
+    fn announcements_paused_and_resumed() {
+        let mut app_state = running_state();
+
+        assert!(app_state.apply(StateEvent::AnnouncementsPaused(true)));
+        assert!(app_state.is_paused);
+        assert_eq!(app_state.status(), AppStatus::Neutral);
+        assert_eq!(app_state.message(), "⏸ Announcements are paused.");
+        assert!(app_state.can_broadcast());
+
+        assert!(!app_state.apply(StateEvent::AnnouncementsPaused(true)));
+
+        assert!(app_state.apply(StateEvent::AnnouncementsPaused(false)));
+        assert_eq!(app_state.status(), AppStatus::Running);
+    }
+
+    #[test]
+    fn config_restored_stops_announcing() {
+        let mut app_state = running_state();
+        app_state.apply(StateEvent::BroadcasterChanged(BroadcasterState::Idle));
+
+        assert!(app_state.apply(StateEvent::ConfigRestored));
+        assert_eq!(app_state.config_phase, ConfigPhase::Restored);
+        assert_eq!(app_state.connection_string, None);
+        assert!(!app_state.can_broadcast());
+        assert_eq!(app_state.status(), AppStatus::Neutral);
+        assert_eq!(app_state.message(), "Original SimConnect.xml restored.");
+    }
+
+    #[test]
+    fn endpoint_health_changes() {
+        let mut app_state = running_state();
+        let listening = ProbeResult::Listening {
+            latency: std::time::Duration::from_millis(3),
+            handshake: Some(true),
+        };
+
+        assert!(
+            app_state.apply(StateEvent::EndpointHealthChanged(EndpointHealth::from(
+                &listening
+            )))
+        );
+        assert_eq!(app_state.endpoint_health, EndpointHealth::Listening);
+        assert!(!app_state.apply(StateEvent::EndpointHealthChanged(EndpointHealth::Listening)));
+
+        assert!(
+            app_state.apply(StateEvent::EndpointHealthChanged(EndpointHealth::from(
+                &ProbeResult::NotAccepting
+            )))
+        );
+        assert_eq!(app_state.endpoint_health, EndpointHealth::NotAccepting);
+        /* The probe is informational, the status stays as it was */
+        assert_eq!(app_state.status(), AppStatus::Running);
+    }
+
+    #[test]
+    fn failed_handshake_is_not_accepting() {
+        let result = ProbeResult::Listening {
+            latency: std::time::Duration::from_millis(3),
+            handshake: Some(false),
+        };
+
+        assert_eq!(EndpointHealth::from(&result), EndpointHealth::NotAccepting);
+    }
+
+    #[test]
+    fn only_changes_are_recorded_and_observed() {
+        let mut app_state = AppState::new();
+        let observer = app_state.subscribe();
+
+        app_state.apply(StateEvent::AnnouncementsPaused(true));
+        app_state.apply(StateEvent::AnnouncementsPaused(true));
+        app_state.apply(StateEvent::AnnouncementsPaused(false));
+
+        assert_eq!(app_state.history().count(), 2);
+        assert_eq!(
+            observer
+                .try_iter()
+                .map(|record| record.event)
+                .collect::<Vec<_>>(),
+            vec![
+                StateEvent::AnnouncementsPaused(true),
+                StateEvent::AnnouncementsPaused(false)
+            ]
+        );
+    }
+
+    #[test]
+    fn connected_clients_are_recorded_for_observers() {
+        let mut app_state = AppState::new();
+        let observer = app_state.subscribe();
+        let address: IpAddr = "192.168.1.20".parse().unwrap();
+
+        assert!(!app_state.apply(StateEvent::ClientsConnected(Vec::new())));
+        assert!(app_state.apply(StateEvent::ClientsConnected(vec![address])));
+
+        let record = observer.try_recv().unwrap();
+        assert_eq!(record.event, StateEvent::ClientsConnected(vec![address]));
+        assert_eq!(record.event.describe(), "Device connected: 192.168.1.20");
+        assert!(observer.try_recv().is_err());
+        assert_eq!(app_state.history().count(), 1);
+    }
+
+    #[test]
+    fn history_is_limited() {
+        let mut app_state = AppState::new();
+
+        for index in 0..STATE_HISTORY_LIMIT + 10 {
+            app_state.apply(StateEvent::AnnouncementsPaused(index % 2 == 0));
+        }
+
+        assert_eq!(app_state.history().count(), STATE_HISTORY_LIMIT);
+    }
 }
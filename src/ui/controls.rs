@@ -0,0 +1,242 @@
+use glyphon::{Attrs, Buffer, Color, Family, FontSystem, Metrics, Shaping, TextArea, TextBounds};
+
+use super::{
+    layout::Layout,
+    rects::{rgb, Rect},
+};
+
+static BUTTON_HEIGHT: f32 = 26.0;
+static BUTTON_PADDING: f32 = 10.0;
+static BUTTON_SPACING: f32 = 8.0;
+static FOCUS_RING_WIDTH: f32 = 2.0;
+static LABEL_METRICS: Metrics = Metrics::new(13.0, 16.0);
+
+struct Button {
+    id: &'static str,
+    label: Buffer,
+    /// Logical pixels from the window's top left corner.
+    left: f32,
+    top: f32,
+    width: f32,
+    is_enabled: bool,
+}
+
+impl Button {
+    fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.left
+            && x < self.left + self.width
+            && y >= self.top
+            && y < self.top + BUTTON_HEIGHT
+    }
+}
+
+/// A row of buttons drawn into the status window, operated by mouse or keyboard.
+/// Activating a button yields its id, the same ids the tray menu uses.
+pub struct Controls {
+    buttons: Vec<Button>,
+    hovered: Option<usize>,
+    pressed: Option<usize>,
+    focused: Option<usize>,
+}
+
+impl Controls {
+    /// Lays out `(id, label)` buttons in a row starting at the logical position.
+    pub fn new(
+        font_system: &mut FontSystem,
+        buttons: &[(&'static str, &'static str)],
+        left: f32,
+        top: f32,
+    ) -> Self {
+        let mut next_left = left;
+
+        let buttons = buttons
+            .iter()
+            .map(|(id, text)| {
+                let mut label = Buffer::new(font_system, LABEL_METRICS);
+                label.set_size(font_system, f32::MAX, LABEL_METRICS.line_height);
+                label.set_text(
+                    font_system,
+                    text,
+                    Attrs::new().family(Family::SansSerif),
+                    Shaping::Advanced,
+                );
+
+                let label_width = label
+                    .layout_runs()
+                    .map(|run| run.line_w)
+                    .fold(0.0, f32::max);
+
+                let button = Button {
+                    id,
+                    label,
+                    left: next_left,
+                    top,
+                    width: label_width.ceil() + 2.0 * BUTTON_PADDING,
+                    is_enabled: true,
+                };
+
+                next_left += button.width + BUTTON_SPACING;
+                button
+            })
+            .collect();
+
+        Controls {
+            buttons,
+            hovered: None,
+            pressed: None,
+            focused: None,
+        }
+    }
+
+    /// Disabled buttons are greyed out and skipped by the keyboard.
+    pub fn set_enabled(&mut self, id: &str, is_enabled: bool) {
+        for (index, button) in self.buttons.iter_mut().enumerate() {
+            if button.id == id {
+                button.is_enabled = is_enabled;
+
+                if !is_enabled && self.focused == Some(index) {
+                    self.focused = None;
+                }
+            }
+        }
+    }
+
+    fn enabled_at(&self, x: f32, y: f32) -> Option<usize> {
+        self.buttons
+            .iter()
+            .position(|button| button.is_enabled && button.contains(x, y))
+    }
+
+    /// Takes the cursor in logical pixels, returns whether a redraw is needed.
+    pub fn pointer_moved(&mut self, x: f32, y: f32) -> bool {
+        let hovered = self.enabled_at(x, y);
+        let is_changed = hovered != self.hovered;
+        self.hovered = hovered;
+        is_changed
+    }
+
+    pub fn pointer_left(&mut self) -> bool {
+        let is_changed = self.hovered.is_some() || self.pressed.is_some();
+        self.hovered = None;
+        self.pressed = None;
+        is_changed
+    }
+
+    pub fn pointer_pressed(&mut self) -> bool {
+        self.pressed = self.hovered;
+
+        if self.pressed.is_some() {
+            self.focused = self.pressed;
+        }
+
+        self.pressed.is_some()
+    }
+
+    /// Returns the id of the button when the press started and ended on it.
+    pub fn pointer_released(&mut self) -> Option<&'static str> {
+        let pressed = self.pressed.take()?;
+
+        (self.hovered == Some(pressed)).then(|| self.buttons[pressed].id)
+    }
+
+    /// Moves the focus to the next enabled button, wrapping around.
+    pub fn focus_next(&mut self, is_backwards: bool) -> bool {
+        let count = self.buttons.len();
+
+        if count == 0 {
+            return false;
+        }
+
+        let start = match (self.focused, is_backwards) {
+            (Some(focused), false) => focused + 1,
+            (Some(focused), true) => focused + count - 1,
+            (None, false) => 0,
+            (None, true) => count - 1,
+        };
+
+        let next = (0..count)
+            .map(|step| {
+                if is_backwards {
+                    (start + count - step) % count
+                } else {
+                    (start + step) % count
+                }
+            })
+            .find(|index| self.buttons[*index].is_enabled);
+
+        let is_changed = next != self.focused;
+        self.focused = next;
+        is_changed
+    }
+
+    pub fn clear_focus(&mut self) -> bool {
+        self.focused.take().is_some()
+    }
+
+    pub fn focused_id(&self) -> Option<&'static str> {
+        self.focused.map(|focused| self.buttons[focused].id)
+    }
+
+    /// Backgrounds and the focus ring, in physical pixels.
+    pub fn rects(&self, layout: &Layout) -> Vec<Rect> {
+        let scale = layout.scale_factor;
+        let mut rects = Vec::new();
+
+        for (index, button) in self.buttons.iter().enumerate() {
+            if self.focused == Some(index) {
+                rects.push(Rect {
+                    left: (button.left - FOCUS_RING_WIDTH) * scale,
+                    top: (button.top - FOCUS_RING_WIDTH) * scale,
+                    width: (button.width + 2.0 * FOCUS_RING_WIDTH) * scale,
+                    height: (BUTTON_HEIGHT + 2.0 * FOCUS_RING_WIDTH) * scale,
+                    color: rgb(0x3b, 0x82, 0xf6),
+                });
+            }
+
+            let color = if !button.is_enabled {
+                rgb(28, 28, 28)
+            } else if self.pressed == Some(index) && self.hovered == Some(index) {
+                rgb(30, 30, 30)
+            } else if self.hovered == Some(index) {
+                rgb(64, 64, 64)
+            } else {
+                rgb(45, 45, 45)
+            };
+
+            rects.push(Rect {
+                left: button.left * scale,
+                top: button.top * scale,
+                width: button.width * scale,
+                height: BUTTON_HEIGHT * scale,
+                color,
+            });
+        }
+
+        rects
+    }
+
+    pub fn text_areas(&self, layout: &Layout) -> Vec<TextArea<'_>> {
+        let scale = layout.scale_factor;
+
+        self.buttons
+            .iter()
+            .map(|button| TextArea {
+                buffer: &button.label,
+                left: (button.left + BUTTON_PADDING) * scale,
+                top: (button.top + (BUTTON_HEIGHT - LABEL_METRICS.line_height) / 2.0) * scale,
+                scale,
+                bounds: TextBounds {
+                    left: (button.left * scale) as i32,
+                    top: (button.top * scale) as i32,
+                    right: ((button.left + button.width) * scale).ceil() as i32,
+                    bottom: ((button.top + BUTTON_HEIGHT) * scale).ceil() as i32,
+                },
+                default_color: if button.is_enabled {
+                    Color::rgb(220, 220, 220)
+                } else {
+                    Color::rgb(90, 90, 90)
+                },
+            })
+            .collect()
+    }
+}
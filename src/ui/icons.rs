@@ -1,6 +1,10 @@
 use std::{io::Read, path::Path};
 
-use crate::utils::file::{load_try_icon, load_window_icon};
+use crate::{
+    utils::file::{load_png, load_window_icon},
+    AppStatus,
+};
+use image::{Rgba, RgbaImage};
 use tray_icon::Icon as TryIcon;
 use winit::window::Icon as WindowIcon;
 
@@ -29,19 +33,98 @@ const ERROR_ICON_PNG: &'static [u8] = include_bytes!(concat!(
     "/static/icon_error.png"
 ));
 
+/// Color of the connected clients badge.
+static CLIENT_BADGE_COLOR: [u8; 3] = [0x3b, 0x82, 0xf6];
+/// Color the status dot flashes with when a packet is sent.
+static PULSE_COLOR: [u8; 3] = [0xff, 0xff, 0xff];
+
+/// Overlays drawn on top of the status icon.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct IconOverlay {
+    pub is_pulsing: bool,
+    pub has_clients: bool,
+}
+
+/// Decoded tray icons, kept as images so overlays can be composed at runtime.
 pub struct TryIcons {
-    pub neutral: TryIcon,
-    pub running: TryIcon,
-    pub warning: TryIcon,
-    pub error: TryIcon,
+    neutral: RgbaImage,
+    running: RgbaImage,
+    warning: RgbaImage,
+    error: RgbaImage,
+    /// Pixels of the status dot with their alpha, where the running icon differs from the neutral one.
+    status_dot: Vec<(u32, u32, u8)>,
+}
+
+fn load_image(png_icon_data: &'static [u8]) -> RgbaImage {
+    let (rgba, width, height) = load_png(png_icon_data);
+    RgbaImage::from_raw(width, height, rgba).expect("Failed to decode try icon")
+}
+
+fn paint(image: &mut RgbaImage, pixels: impl Iterator<Item = (u32, u32, u8)>, color: [u8; 3]) {
+    for (x, y, alpha) in pixels {
+        if x < image.width() && y < image.height() {
+            let pixel = image.get_pixel_mut(x, y);
+            let blend = |under: u8, over: u8| {
+                ((over as u32 * alpha as u32 + under as u32 * (255 - alpha as u32)) / 255) as u8
+            };
+
+            *pixel = Rgba([
+                blend(pixel[0], color[0]),
+                blend(pixel[1], color[1]),
+                blend(pixel[2], color[2]),
+                pixel[3].max(alpha),
+            ]);
+        }
+    }
+}
+
+impl TryIcons {
+    /// Status icon with the overlays, the pulse lights up the status dot
+    /// and the clients badge is the same dot mirrored to the bottom corner.
+    pub fn compose(&self, status: AppStatus, overlay: IconOverlay) -> TryIcon {
+        let mut image = match status {
+            AppStatus::Neutral => self.neutral.clone(),
+            AppStatus::Running => self.running.clone(),
+            AppStatus::Warning => self.warning.clone(),
+            AppStatus::Error => self.error.clone(),
+        };
+
+        if overlay.is_pulsing {
+            paint(&mut image, self.status_dot.iter().copied(), PULSE_COLOR);
+        }
+
+        if overlay.has_clients {
+            let height = image.height();
+            paint(
+                &mut image,
+                self.status_dot
+                    .iter()
+                    .map(|(x, y, alpha)| (*x, height - 1 - y, *alpha)),
+                CLIENT_BADGE_COLOR,
+            );
+        }
+
+        let (width, height) = image.dimensions();
+        TryIcon::from_rgba(image.into_raw(), width, height).expect("Failed to open try icon")
+    }
 }
 
 pub fn get_try_icons() -> TryIcons {
+    let neutral = load_image(NEUTRAL_ICON_PNG);
+    let running = load_image(RUNNING_ICON_PNG);
+
+    let status_dot = running
+        .enumerate_pixels()
+        .filter(|(x, y, pixel)| neutral.get_pixel(*x, *y) != *pixel)
+        .map(|(x, y, pixel)| (x, y, pixel[3]))
+        .collect();
+
     TryIcons {
-        neutral: load_try_icon(NEUTRAL_ICON_PNG),
-        running: load_try_icon(RUNNING_ICON_PNG),
-        warning: load_try_icon(WARNING_ICON_PNG),
-        error: load_try_icon(ERROR_ICON_PNG),
+        neutral,
+        running,
+        warning: load_image(WARNING_ICON_PNG),
+        error: load_image(ERROR_ICON_PNG),
+        status_dot,
     }
 }
 
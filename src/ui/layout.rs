@@ -0,0 +1,110 @@
+use glyphon::{Buffer, Color, FontSystem, Resolution, TextArea, TextBounds};
+use winit::dpi::PhysicalSize;
+
+/// Distance along one axis, in logical pixels.
+#[derive(Debug, Clone, Copy)]
+pub enum Offset {
+    /// From the left or top edge of the window.
+    Start(f32),
+    /// From the right or bottom edge of the window.
+    End(f32),
+}
+
+/// Where a text block sits, relative to the window edges.
+#[derive(Debug, Clone, Copy)]
+pub struct Slot {
+    pub left: Offset,
+    pub top: Offset,
+    /// Space kept free to the right edge, the text wraps before it.
+    pub right_margin: f32,
+    /// Space kept free to the bottom edge, the text is clipped there.
+    pub bottom_margin: f32,
+}
+
+impl Slot {
+    pub const fn at(left: f32, top: f32) -> Self {
+        Slot {
+            left: Offset::Start(left),
+            top: Offset::Start(top),
+            right_margin: 0.0,
+            bottom_margin: 0.0,
+        }
+    }
+}
+
+/// Window size and scale factor the text is laid out for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Layout {
+    pub size: PhysicalSize<u32>,
+    pub scale_factor: f32,
+}
+
+fn resolve(offset: Offset, length: f32) -> f32 {
+    match offset {
+        Offset::Start(distance) => distance,
+        Offset::End(distance) => length - distance,
+    }
+}
+
+impl Layout {
+    pub fn new(size: PhysicalSize<u32>, scale_factor: f64) -> Self {
+        Layout {
+            size,
+            scale_factor: scale_factor as f32,
+        }
+    }
+
+    /// A minimized window has no area to draw into.
+    pub fn is_empty(&self) -> bool {
+        self.size.width == 0 || self.size.height == 0
+    }
+
+    pub fn resolution(&self) -> Resolution {
+        Resolution {
+            width: self.size.width,
+            height: self.size.height,
+        }
+    }
+
+    /// Slot position and size in logical pixels, never negative.
+    fn rect(&self, slot: &Slot) -> (f32, f32, f32, f32) {
+        let width = self.size.width as f32 / self.scale_factor;
+        let height = self.size.height as f32 / self.scale_factor;
+
+        let left = resolve(slot.left, width);
+        let top = resolve(slot.top, height);
+
+        (
+            left,
+            top,
+            (width - slot.right_margin - left).max(0.0),
+            (height - slot.bottom_margin - top).max(0.0),
+        )
+    }
+
+    /// Sizes the buffer to its slot, so the text wraps at the slot's edge.
+    pub fn fit(&self, font_system: &mut FontSystem, buffer: &mut Buffer, slot: &Slot) {
+        let (_, _, width, height) = self.rect(slot);
+        buffer.set_size(font_system, width, height);
+    }
+
+    /// Text area of a fitted buffer, scaled to the display.
+    pub fn text_area<'a>(&self, buffer: &'a Buffer, slot: &Slot, color: Color) -> TextArea<'a> {
+        let (left, top, width, height) = self.rect(slot);
+        let scale = self.scale_factor;
+
+        TextArea {
+            buffer,
+            left: left * scale,
+            top: top * scale,
+            scale,
+            bounds: TextBounds {
+                left: (left * scale) as i32,
+                top: (top * scale) as i32,
+                right: ((left + width) * scale).ceil() as i32,
+                bottom: ((top + height) * scale).ceil() as i32,
+            },
+            default_color: color,
+        }
+    }
+}
@@ -1,2 +1,8 @@
+pub mod controls;
 pub mod icons;
+pub mod layout;
+pub mod notification;
+pub mod rects;
+pub mod renderer;
+pub mod software;
 pub mod system_try;
@@ -0,0 +1,82 @@
+use notify_rust::Notification;
+use std::sync::mpsc;
+
+use crate::{
+    settings::NotificationSettings,
+    state::{BroadcasterState, RestartReason, StateEvent, StateRecord},
+    APP_TITLE,
+};
+
+/// Shows a desktop notification, failures are only logged.
+pub fn show_notification(summary: &str, body: &str) {
+    if let Err(e) = Notification::new()
+        .appname(APP_TITLE)
+        .summary(summary)
+        .body(body)
+        .show()
+    {
+        log::warn!("Unable to show notification: {}", e);
+    }
+}
+
+fn describe_restart_reason(reason: RestartReason) -> &'static str {
+    match reason {
+        RestartReason::StartOrder => {
+            "SimConnect.xml was changed after the simulator started. Restart the simulator and run this client before starting it next time."
+        }
+        RestartReason::AddressChanged => {
+            "The network address changed. Restart the simulator to use the new SimConnect.xml."
+        }
+        RestartReason::ConfigReapplied => {
+            "SimConnect.xml was re-applied. Restart the simulator to load it."
+        }
+    }
+}
+
+/// Turns the state changes of the enabled categories into notifications.
+pub fn notification_thread(settings: NotificationSettings, records: mpsc::Receiver<StateRecord>) {
+    /* Only a broadcaster that failed before can recover */
+    let mut is_broadcaster_failed = false;
+
+    for record in records {
+        match record.event {
+            StateEvent::ConfigApplied {
+                restart_required: Some(reason),
+                ..
+            } if settings.config_changed => {
+                show_notification("Restart the simulator", describe_restart_reason(reason));
+            }
+            StateEvent::BroadcasterChanged(BroadcasterState::Failed) => {
+                if settings.broadcaster && !is_broadcaster_failed {
+                    show_notification(
+                        "Broadcasting failed",
+                        "Remote devices can't discover SimConnect until broadcasting recovers.",
+                    );
+                }
+                is_broadcaster_failed = true;
+            }
+            StateEvent::BroadcasterChanged(BroadcasterState::Running) => {
+                if settings.broadcaster && is_broadcaster_failed {
+                    show_notification("Broadcasting recovered", "SimConnect is announced again.");
+                }
+                is_broadcaster_failed = false;
+            }
+            StateEvent::ClientsConnected(addresses) if settings.new_client => {
+                for address in addresses {
+                    show_notification(
+                        "Device connected",
+                        &format!("{} connected to SimConnect.", address),
+                    );
+                }
+            }
+            StateEvent::SimPresenceChanged(is_running) if settings.sim_presence => {
+                if is_running {
+                    show_notification("Simulator started", "The simulator is running.");
+                } else {
+                    show_notification("Simulator stopped", "The simulator is no longer running.");
+                }
+            }
+            _ => {}
+        }
+    }
+}
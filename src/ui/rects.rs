@@ -0,0 +1,164 @@
+use std::borrow::Cow;
+use wgpu::{
+    vertex_attr_array, BlendState, Buffer, BufferAddress, BufferDescriptor, BufferUsages,
+    ColorTargetState, ColorWrites, Device, FragmentState, MultisampleState,
+    PipelineLayoutDescriptor, PrimitiveState, Queue, RenderPass, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, TextureFormat,
+    VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+static SHADER: &str = "
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) position: vec2<f32>, @location(1) color: vec4<f32>) -> VertexOutput {
+    var output: VertexOutput;
+    output.position = vec4<f32>(position, 0.0, 1.0);
+    output.color = color;
+    return output;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    return input.color;
+}
+";
+
+/// Two position and four color floats.
+static VERTEX_SIZE: BufferAddress = 6 * 4;
+
+/// Filled rectangle in physical pixels.
+#[derive(Debug, Clone, Copy)]
+pub struct Rect {
+    pub left: f32,
+    pub top: f32,
+    pub width: f32,
+    pub height: f32,
+    pub color: [f32; 4],
+}
+
+/// Color from sRGB components, the surface expects linear values.
+pub fn rgb(r: u8, g: u8, b: u8) -> [f32; 4] {
+    let linear = |component: u8| (component as f32 / 255.0).powf(2.2);
+    [linear(r), linear(g), linear(b), 1.0]
+}
+
+/// Window background, linear like `rgb`.
+pub static BACKGROUND: [f32; 4] = [0.02, 0.02, 0.02, 1.0];
+
+/// Draws flat rectangles below the text, e.g. button backgrounds.
+pub struct RectRenderer {
+    pipeline: RenderPipeline,
+    vertex_buffer: Buffer,
+    vertex_count: u32,
+}
+
+fn create_vertex_buffer(device: &Device, size: BufferAddress) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some("rects"),
+        size,
+        usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+impl RectRenderer {
+    pub fn new(device: &Device, format: TextureFormat) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("rects"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(SHADER)),
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("rects"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("rects"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[VertexBufferLayout {
+                    array_stride: VERTEX_SIZE,
+                    step_mode: VertexStepMode::Vertex,
+                    attributes: &vertex_attr_array![0 => Float32x2, 1 => Float32x4],
+                }],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+
+        RectRenderer {
+            pipeline,
+            vertex_buffer: create_vertex_buffer(device, VERTEX_SIZE * 6),
+            vertex_count: 0,
+        }
+    }
+
+    /// Uploads the rectangles for the next `render`, as two triangles each.
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        width: u32,
+        height: u32,
+        rects: &[Rect],
+    ) {
+        let to_x = |x: f32| x / width as f32 * 2.0 - 1.0;
+        let to_y = |y: f32| 1.0 - y / height as f32 * 2.0;
+
+        let vertices: Vec<u8> = rects
+            .iter()
+            .flat_map(|rect| {
+                let (left, right) = (to_x(rect.left), to_x(rect.left + rect.width));
+                let (top, bottom) = (to_y(rect.top), to_y(rect.top + rect.height));
+
+                [
+                    (left, top),
+                    (left, bottom),
+                    (right, top),
+                    (right, top),
+                    (left, bottom),
+                    (right, bottom),
+                ]
+                .into_iter()
+                .flat_map(move |(x, y)| [x, y].into_iter().chain(rect.color))
+            })
+            .flat_map(f32::to_le_bytes)
+            .collect();
+
+        if vertices.len() as BufferAddress > self.vertex_buffer.size() {
+            self.vertex_buffer = create_vertex_buffer(device, vertices.len() as BufferAddress);
+        }
+
+        queue.write_buffer(&self.vertex_buffer, 0, &vertices);
+        self.vertex_count = (vertices.len() as BufferAddress / VERTEX_SIZE) as u32;
+    }
+
+    pub fn render<'a>(&'a self, rpass: &mut RenderPass<'a>) {
+        if self.vertex_count == 0 {
+            return;
+        }
+
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        rpass.draw(0..self.vertex_count, 0..1);
+    }
+}
@@ -0,0 +1,59 @@
+use glyphon::{FontSystem, SwashCache, TextArea};
+use winit::window::Window;
+
+use super::{layout::Layout, rects::Rect, software::SoftwareRenderer};
+use crate::utils::wgpu::{configure_wgpu, GpuRenderer};
+
+/// Whatever could be set up to draw the status window.
+pub enum Renderer<'w> {
+    Gpu(Box<GpuRenderer<'w>>),
+    Software(SoftwareRenderer<'w>),
+    /// Nothing can draw into the window, the app lives in the tray only.
+    Unavailable,
+}
+
+/// Tries wgpu, then the CPU renderer, and settles for the tray when both fail.
+pub async fn create_renderer(window: &Window) -> Renderer<'_> {
+    let gpu_error = match configure_wgpu(window).await {
+        Ok(renderer) => return Renderer::Gpu(Box::new(renderer)),
+        Err(error) => error,
+    };
+
+    log::warn!("{}, falling back to software rendering", gpu_error);
+
+    match SoftwareRenderer::new(window) {
+        Ok(renderer) => {
+            log::info!("Rendering on the CPU");
+            Renderer::Software(renderer)
+        }
+        Err(error) => {
+            log::error!("{}, the status window is unavailable", error);
+            Renderer::Unavailable
+        }
+    }
+}
+
+impl Renderer<'_> {
+    pub fn is_available(&self) -> bool {
+        !matches!(self, Renderer::Unavailable)
+    }
+
+    pub fn draw(
+        &mut self,
+        font_system: &mut FontSystem,
+        swash_cache: &mut SwashCache,
+        layout: &Layout,
+        text_areas: Vec<TextArea>,
+        rects: &[Rect],
+    ) -> Result<(), String> {
+        match self {
+            Renderer::Gpu(renderer) => {
+                renderer.draw(font_system, swash_cache, layout, text_areas, rects)
+            }
+            Renderer::Software(renderer) => {
+                renderer.draw(font_system, swash_cache, layout, text_areas, rects)
+            }
+            Renderer::Unavailable => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use glyphon::{Color, FontSystem, SwashCache, TextArea};
+use softbuffer::{Context, Surface};
+use std::num::NonZeroU32;
+use winit::window::Window;
+
+use super::{
+    layout::Layout,
+    rects::{Rect, BACKGROUND},
+};
+
+/// Draws the window on the CPU into a softbuffer surface, for machines without any wgpu adapter.
+/// Text goes through the same layout and glyph cache as on the GPU, just blended here.
+pub struct SoftwareRenderer<'w> {
+    surface: Surface<&'w Window, &'w Window>,
+}
+
+/// Pixels are `0RGB`, in sRGB like the text colors.
+struct Canvas<'a> {
+    pixels: &'a mut [u32],
+    width: i32,
+    height: i32,
+}
+
+fn to_srgb(component: f32) -> u32 {
+    (component.clamp(0.0, 1.0).powf(1.0 / 2.2) * 255.0).round() as u32
+}
+
+/// Linear colors from `rgb` back to the surface's sRGB.
+fn linear_to_color(color: [f32; 4]) -> Color {
+    let [r, g, b, a] = color;
+    Color::rgba(
+        to_srgb(r) as u8,
+        to_srgb(g) as u8,
+        to_srgb(b) as u8,
+        (a.clamp(0.0, 1.0) * 255.0).round() as u8,
+    )
+}
+
+impl Canvas<'_> {
+    fn blend(&mut self, x: i32, y: i32, color: Color) {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height || color.a() == 0 {
+            return;
+        }
+
+        let pixel = &mut self.pixels[(y * self.width + x) as usize];
+        let alpha = color.a() as u32;
+        let mix = |source: u8, shift: u32| {
+            let target = (*pixel >> shift) & 0xff;
+            ((source as u32 * alpha + target * (255 - alpha)) / 255) << shift
+        };
+
+        *pixel = mix(color.r(), 16) | mix(color.g(), 8) | mix(color.b(), 0);
+    }
+
+    fn fill(&mut self, rect: &Rect) {
+        let color = linear_to_color(rect.color);
+        let left = rect.left.round() as i32;
+        let top = rect.top.round() as i32;
+        let right = (rect.left + rect.width).round() as i32;
+        let bottom = (rect.top + rect.height).round() as i32;
+
+        for y in top.max(0)..bottom.min(self.height) {
+            for x in left.max(0)..right.min(self.width) {
+                self.blend(x, y, color);
+            }
+        }
+    }
+
+    /// Glyphs placed as glyphon does, clipped to the area's bounds.
+    fn text(
+        &mut self,
+        font_system: &mut FontSystem,
+        swash_cache: &mut SwashCache,
+        text_area: &TextArea,
+    ) {
+        let bounds = text_area.bounds;
+
+        for run in text_area.buffer.layout_runs() {
+            let line_y = (run.line_y * text_area.scale).round() as i32;
+
+            for glyph in run.glyphs.iter() {
+                let physical_glyph =
+                    glyph.physical((text_area.left, text_area.top), text_area.scale);
+                let color = glyph.color_opt.unwrap_or(text_area.default_color);
+
+                swash_cache.with_pixels(
+                    font_system,
+                    physical_glyph.cache_key,
+                    color,
+                    |x, y, color| {
+                        let x = physical_glyph.x + x;
+                        let y = line_y + physical_glyph.y + y;
+
+                        if x >= bounds.left
+                            && x < bounds.right
+                            && y >= bounds.top
+                            && y < bounds.bottom
+                        {
+                            self.blend(x, y, color);
+                        }
+                    },
+                );
+            }
+        }
+    }
+}
+
+impl<'w> SoftwareRenderer<'w> {
+    pub fn new(window: &'w Window) -> Result<Self, String> {
+        let context = Context::new(window)
+            .map_err(|e| format!("Error creating softbuffer context: {}", e))?;
+        let surface = Surface::new(&context, window)
+            .map_err(|e| format!("Error creating softbuffer surface: {}", e))?;
+
+        Ok(SoftwareRenderer { surface })
+    }
+
+    pub fn draw(
+        &mut self,
+        font_system: &mut FontSystem,
+        swash_cache: &mut SwashCache,
+        layout: &Layout,
+        text_areas: Vec<TextArea>,
+        rects: &[Rect],
+    ) -> Result<(), String> {
+        let (Some(width), Some(height)) = (
+            NonZeroU32::new(layout.size.width),
+            NonZeroU32::new(layout.size.height),
+        ) else {
+            return Ok(());
+        };
+
+        self.surface
+            .resize(width, height)
+            .map_err(|e| format!("Error resizing softbuffer surface: {}", e))?;
+
+        let mut buffer = self
+            .surface
+            .buffer_mut()
+            .map_err(|e| format!("Error acquiring softbuffer frame: {}", e))?;
+
+        let background = linear_to_color(BACKGROUND);
+        buffer.fill(background.0 & 0xff_ff_ff);
+
+        let mut canvas = Canvas {
+            pixels: &mut buffer,
+            width: width.get() as i32,
+            height: height.get() as i32,
+        };
+
+        for rect in rects {
+            canvas.fill(rect);
+        }
+
+        for text_area in &text_areas {
+            canvas.text(font_system, swash_cache, text_area);
+        }
+
+        buffer
+            .present()
+            .map_err(|e| format!("Error presenting softbuffer frame: {}", e))
+    }
+}
@@ -1,15 +1,28 @@
 use std::{collections::HashMap, path::Path};
 
 use tray_icon::{
-    menu::{Menu, MenuId, MenuItem, PredefinedMenuItem},
+    menu::{CheckMenuItem, Menu, MenuId, MenuItem, PredefinedMenuItem},
     Icon, TrayIcon, TrayIconBuilder,
 };
 
-use crate::{AppStatus, APP_TITLE};
+use crate::{
+    state::{AppState, BroadcasterState, ConfigPhase},
+    AppStatus, APP_TITLE,
+};
 
-use super::icons::{get_try_icons, TryIcons};
+use super::icons::{get_try_icons, IconOverlay, TryIcons};
 
 pub static MENU_ITEM_STATUS_ID: &str = "STATUS";
+pub static MENU_ITEM_COPY_CONNECTION_ID: &str = "COPY_CONNECTION";
+pub static MENU_ITEM_RESTART_BROADCAST_ID: &str = "RESTART_BROADCAST";
+pub static MENU_ITEM_REAPPLY_CONFIG_ID: &str = "REAPPLY_CONFIG";
+pub static MENU_ITEM_OPEN_CONFIG_FOLDER_ID: &str = "OPEN_CONFIG_FOLDER";
+pub static MENU_ITEM_OPEN_SETTINGS_ID: &str = "OPEN_SETTINGS";
+pub static MENU_ITEM_RESTORE_CONFIG_ID: &str = "RESTORE_CONFIG";
+pub static MENU_ITEM_PAUSE_ID: &str = "PAUSE";
+pub static MENU_ITEM_AUTOSTART_ID: &str = "AUTOSTART";
+pub static MENU_ITEM_LOG_ID: &str = "LOG";
+pub static MENU_ITEM_DIAGNOSTICS_ID: &str = "DIAGNOSTICS";
 pub static MENU_ITEM_EXIT_ID: &str = "EXIT";
 
 enum MenuItemId {
@@ -17,9 +30,38 @@ enum MenuItemId {
     Exit,
 }
 
+/// Everything the tray shows besides the application state.
+pub struct TryMenuState {
+    pub has_config_file: bool,
+    pub has_config_backup: bool,
+    pub is_autostart_enabled: bool,
+    pub seconds_since_last_packet: Option<u64>,
+    pub client_count: usize,
+    /// A packet was just sent.
+    pub is_pulsing: bool,
+}
+
 pub struct SystemTry {
     instance: TrayIcon,
     icons: TryIcons,
+    /// Icon currently shown, so it is only replaced when it changes.
+    shown_icon: Option<(AppStatus, IconOverlay)>,
+    status_menu_item: MenuItem,
+    connection_menu_item: MenuItem,
+    copy_connection_menu_item: MenuItem,
+    restart_broadcast_menu_item: MenuItem,
+    open_config_folder_menu_item: MenuItem,
+    restore_config_menu_item: MenuItem,
+    pause_menu_item: CheckMenuItem,
+    autostart_menu_item: CheckMenuItem,
+}
+
+fn menu_item(id: &str, text: &str) -> MenuItem {
+    MenuItem::with_id(MenuId(id.to_string()), text, true, None)
+}
+
+fn check_menu_item(id: &str, text: &str) -> CheckMenuItem {
+    CheckMenuItem::with_id(MenuId(id.to_string()), text, true, false, None)
 }
 
 impl SystemTry {
@@ -27,45 +69,151 @@ impl SystemTry {
         let icons = get_try_icons();
         let menu = Box::new(Menu::new());
 
-        let title_menu_item = MenuItem::with_id(
-            MenuId(MENU_ITEM_STATUS_ID.to_string()),
-            APP_TITLE,
-            true,
-            None,
+        let title_menu_item = menu_item(MENU_ITEM_STATUS_ID, APP_TITLE);
+        /* Informational lines, never clickable */
+        let status_menu_item = MenuItem::new("Checking...", false, None);
+        let connection_menu_item = MenuItem::new("Not announced", false, None);
+
+        let copy_connection_menu_item =
+            menu_item(MENU_ITEM_COPY_CONNECTION_ID, "Copy connection string");
+        let restart_broadcast_menu_item =
+            menu_item(MENU_ITEM_RESTART_BROADCAST_ID, "Restart broadcasting");
+        let reapply_config_menu_item =
+            menu_item(MENU_ITEM_REAPPLY_CONFIG_ID, "Re-apply SimConnect config");
+        let open_config_folder_menu_item = menu_item(
+            MENU_ITEM_OPEN_CONFIG_FOLDER_ID,
+            "Open SimConnect.xml folder",
         );
-        let separator_menu_item = PredefinedMenuItem::separator();
-        let exit_menu_item = MenuItem::with_id(
-            MenuId(MENU_ITEM_EXIT_ID.to_string()),
-            "Exit".to_string(),
-            true,
-            None,
+        let open_settings_menu_item = menu_item(MENU_ITEM_OPEN_SETTINGS_ID, "Open settings");
+        let restore_config_menu_item =
+            menu_item(MENU_ITEM_RESTORE_CONFIG_ID, "Restore original config");
+        let pause_menu_item = check_menu_item(MENU_ITEM_PAUSE_ID, "Pause announcements");
+        let autostart_menu_item = check_menu_item(
+            MENU_ITEM_AUTOSTART_ID,
+            if cfg!(windows) {
+                "Start with Windows"
+            } else {
+                "Start at login"
+            },
         );
+        let log_menu_item = menu_item(MENU_ITEM_LOG_ID, "View log");
+        let diagnostics_menu_item = menu_item(MENU_ITEM_DIAGNOSTICS_ID, "Export diagnostics");
+        let exit_menu_item = menu_item(MENU_ITEM_EXIT_ID, "Exit");
 
         menu.append(&title_menu_item);
-        menu.append(&separator_menu_item);
+        menu.append(&status_menu_item);
+        menu.append(&connection_menu_item);
+        menu.append(&PredefinedMenuItem::separator());
+        menu.append(&copy_connection_menu_item);
+        menu.append(&restart_broadcast_menu_item);
+        menu.append(&reapply_config_menu_item);
+        menu.append(&open_config_folder_menu_item);
+        menu.append(&open_settings_menu_item);
+        menu.append(&restore_config_menu_item);
+        menu.append(&PredefinedMenuItem::separator());
+        menu.append(&pause_menu_item);
+        menu.append(&autostart_menu_item);
+        menu.append(&PredefinedMenuItem::separator());
+        menu.append(&log_menu_item);
+        menu.append(&diagnostics_menu_item);
+        menu.append(&PredefinedMenuItem::separator());
         menu.append(&exit_menu_item);
 
         let instance = TrayIconBuilder::new()
             .with_menu(menu)
             .with_tooltip(APP_TITLE)
-            .with_icon(icons.neutral.clone())
+            .with_icon(icons.compose(AppStatus::Neutral, IconOverlay::default()))
             .build()
             .unwrap();
 
         instance.set_visible(true);
 
-        SystemTry { icons, instance }
+        SystemTry {
+            icons,
+            instance,
+            shown_icon: None,
+            status_menu_item,
+            connection_menu_item,
+            copy_connection_menu_item,
+            restart_broadcast_menu_item,
+            open_config_folder_menu_item,
+            restore_config_menu_item,
+            pause_menu_item,
+            autostart_menu_item,
+        }
     }
 
-    pub fn set_status(&mut self, status: AppStatus) {
-        let new_icon = match status {
-            AppStatus::Neutral => self.icons.neutral.clone(),
-            AppStatus::Running => self.icons.running.clone(),
-            AppStatus::Warning => self.icons.warning.clone(),
-            AppStatus::Error => self.icons.error.clone(),
-        };
+    pub fn set_status(&mut self, status: AppStatus, overlay: IconOverlay) {
+        if self.shown_icon == Some((status, overlay)) {
+            return;
+        }
 
         // Update the instance's icon
-        self.instance.set_icon(Some(new_icon));
+        self.instance
+            .set_icon(Some(self.icons.compose(status, overlay)));
+        self.shown_icon = Some((status, overlay));
+    }
+
+    fn tooltip(app_state: &AppState, menu_state: &TryMenuState) -> String {
+        let mut lines = vec![APP_TITLE.to_string(), app_state.message().to_string()];
+
+        if let Some(connection_string) = &app_state.connection_string {
+            lines.push(format!("Announced on {}", connection_string));
+        }
+
+        lines.push(format!("Simulator: {}", app_state.sim_presence.label()));
+
+        if let Some(seconds) = menu_state.seconds_since_last_packet {
+            lines.push(format!("Last packet {}s ago", seconds));
+        }
+
+        if menu_state.client_count > 0 {
+            lines.push(format!("{} connected", menu_state.client_count));
+        }
+
+        lines.join("\n")
+    }
+
+    /// Refreshes the icon, the tooltip, the status lines and which actions are available.
+    pub fn update(&mut self, app_state: &AppState, menu_state: &TryMenuState) {
+        let status = app_state.status();
+        self.set_status(
+            status,
+            IconOverlay {
+                is_pulsing: menu_state.is_pulsing,
+                has_clients: menu_state.client_count > 0,
+            },
+        );
+
+        if let Err(e) = self
+            .instance
+            .set_tooltip(Some(Self::tooltip(app_state, menu_state)))
+        {
+            log::debug!("Unable to update tray tooltip: {}", e);
+        }
+
+        self.status_menu_item.set_text(app_state.message());
+        self.connection_menu_item.set_text(
+            app_state
+                .connection_string
+                .as_deref()
+                .unwrap_or("Not announced"),
+        );
+
+        self.copy_connection_menu_item
+            .set_enabled(app_state.connection_string.is_some() && status != AppStatus::Error);
+        self.restart_broadcast_menu_item
+            .set_enabled(app_state.can_broadcast());
+        self.open_config_folder_menu_item
+            .set_enabled(menu_state.has_config_file);
+        self.restore_config_menu_item.set_enabled(
+            menu_state.has_config_backup && app_state.config_phase != ConfigPhase::Restored,
+        );
+
+        self.pause_menu_item
+            .set_enabled(app_state.is_paused || app_state.broadcaster == BroadcasterState::Running);
+        self.pause_menu_item.set_checked(app_state.is_paused);
+        self.autostart_menu_item
+            .set_checked(menu_state.is_autostart_enabled);
     }
 }
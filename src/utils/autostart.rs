@@ -0,0 +1,326 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::APP_TITLE;
+
+/// Registration of the client to start when the user logs in.
+pub trait Autostart {
+    fn is_enabled(&self) -> bool;
+    fn set_enabled(&self, enabled: bool) -> Result<(), String>;
+}
+
+fn current_executable() -> Result<PathBuf, String> {
+    std::env::current_exe().map_err(|e| format!("Error locating executable: {}", e))
+}
+
+#[cfg(windows)]
+static RUN_KEY: &str = "Software\\Microsoft\\Windows\\CurrentVersion\\Run";
+
+#[cfg(windows)]
+fn to_wide(value: &str) -> Vec<u16> {
+    value.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+#[cfg(windows)]
+fn open_run_key(access: u32) -> Result<windows_sys::Win32::System::Registry::HKEY, String> {
+    use windows_sys::Win32::System::Registry::{RegOpenKeyExW, HKEY, HKEY_CURRENT_USER};
+
+    let mut key: HKEY = 0;
+    let result = unsafe {
+        RegOpenKeyExW(
+            HKEY_CURRENT_USER,
+            to_wide(RUN_KEY).as_ptr(),
+            0,
+            access,
+            &mut key,
+        )
+    };
+
+    if result != 0 {
+        return Err(format!("Error opening the Run registry key: {}", result));
+    }
+
+    Ok(key)
+}
+
+/// Entry in the user's `Run` registry key.
+#[cfg(windows)]
+pub struct RegistryAutostart {
+    executable: PathBuf,
+}
+
+#[cfg(windows)]
+impl Autostart for RegistryAutostart {
+    fn is_enabled(&self) -> bool {
+        use std::ptr::null_mut;
+        use windows_sys::Win32::System::Registry::{RegCloseKey, RegQueryValueExW, KEY_READ};
+
+        let Ok(key) = open_run_key(KEY_READ) else {
+            return false;
+        };
+
+        let value_name = to_wide(APP_TITLE);
+        let result = unsafe {
+            RegQueryValueExW(
+                key,
+                value_name.as_ptr(),
+                null_mut(),
+                null_mut(),
+                null_mut(),
+                null_mut(),
+            )
+        };
+
+        unsafe { RegCloseKey(key) };
+
+        result == 0
+    }
+
+    fn set_enabled(&self, enabled: bool) -> Result<(), String> {
+        use windows_sys::Win32::System::Registry::{
+            RegCloseKey, RegDeleteValueW, RegSetValueExW, KEY_SET_VALUE, REG_SZ,
+        };
+
+        let key = open_run_key(KEY_SET_VALUE)?;
+        let value_name = to_wide(APP_TITLE);
+
+        let result = if enabled {
+            let command = to_wide(&format!("\"{}\"", self.executable.display()));
+            unsafe {
+                RegSetValueExW(
+                    key,
+                    value_name.as_ptr(),
+                    0,
+                    REG_SZ,
+                    command.as_ptr() as *const u8,
+                    (command.len() * 2) as u32,
+                )
+            }
+        } else {
+            unsafe { RegDeleteValueW(key, value_name.as_ptr()) }
+        };
+
+        unsafe { RegCloseKey(key) };
+
+        if result != 0 {
+            return Err(format!("Error updating the Run registry key: {}", result));
+        }
+
+        Ok(())
+    }
+}
+
+/// Desktop entry in the XDG autostart directory, used by Linux desktops.
+pub struct XdgAutostart {
+    autostart_dir: PathBuf,
+    executable: PathBuf,
+}
+
+/// Quotes an `Exec` argument as the desktop entry specification requires.
+/// Escapes are doubled, once for the quoting and once for the string value.
+fn quote_exec_argument(argument: &str) -> String {
+    let escaped: String = argument
+        .chars()
+        .map(|character| match character {
+            '"' | '`' | '$' => format!("\\\\{}", character),
+            '\\' => "\\\\\\\\".to_string(),
+            '%' => "%%".to_string(),
+            _ => character.to_string(),
+        })
+        .collect();
+
+    format!("\"{}\"", escaped)
+}
+
+impl XdgAutostart {
+    pub fn new(autostart_dir: &Path, executable: &Path) -> Self {
+        XdgAutostart {
+            autostart_dir: autostart_dir.to_path_buf(),
+            executable: executable.to_path_buf(),
+        }
+    }
+
+    /// `$XDG_CONFIG_HOME/autostart` of the current user.
+    pub fn for_current_user() -> Result<Self, String> {
+        let autostart_dir = dirs::config_dir()
+            .map(|dir| dir.join("autostart"))
+            .ok_or_else(|| "Unable to determine user config directory.".to_string())?;
+
+        Ok(XdgAutostart::new(&autostart_dir, &current_executable()?))
+    }
+
+    pub fn entry_path(&self) -> PathBuf {
+        self.autostart_dir.join(format!("{}.desktop", APP_TITLE))
+    }
+
+    fn entry(&self) -> String {
+        format!(
+            "[Desktop Entry]\n\
+             Type=Application\n\
+             Name={}\n\
+             Exec={}\n\
+             Terminal=false\n\
+             X-GNOME-Autostart-enabled=true\n",
+            APP_TITLE,
+            quote_exec_argument(&self.executable.to_string_lossy())
+        )
+    }
+}
+
+impl Autostart for XdgAutostart {
+    /// An entry hidden by the desktop's own settings counts as disabled.
+    fn is_enabled(&self) -> bool {
+        match fs::read_to_string(self.entry_path()) {
+            Ok(content) => !content.lines().any(|line| {
+                let line = line.trim();
+                line == "Hidden=true" || line == "X-GNOME-Autostart-enabled=false"
+            }),
+            Err(_) => false,
+        }
+    }
+
+    fn set_enabled(&self, enabled: bool) -> Result<(), String> {
+        let entry_path = self.entry_path();
+
+        if enabled {
+            fs::create_dir_all(&self.autostart_dir)
+                .map_err(|e| format!("Error creating autostart directory: {}", e))?;
+            fs::write(&entry_path, self.entry())
+                .map_err(|e| format!("Error writing autostart entry: {}", e))
+        } else if entry_path.exists() {
+            fs::remove_file(&entry_path)
+                .map_err(|e| format!("Error removing autostart entry: {}", e))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Autostart of the current platform.
+#[cfg(windows)]
+pub fn platform_autostart() -> Result<Box<dyn Autostart>, String> {
+    Ok(Box::new(RegistryAutostart {
+        executable: current_executable()?,
+    }))
+}
+
+#[cfg(not(windows))]
+pub fn platform_autostart() -> Result<Box<dyn Autostart>, String> {
+    Ok(Box::new(XdgAutostart::for_current_user()?))
+}
+
+pub fn is_autostart_enabled() -> bool {
+    platform_autostart()
+        .map(|autostart| autostart.is_enabled())
+        .unwrap_or(false)
+}
+
+pub fn set_autostart(enabled: bool) -> Result<(), String> {
+    platform_autostart()?.set_enabled(enabled)?;
+
+    log::info!("Autostart {}", if enabled { "enabled" } else { "disabled" });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fresh autostart directory below the system temp dir, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "{}-autostart-{}-{}",
+                APP_TITLE,
+                name,
+                std::process::id()
+            ));
+            fs::remove_dir_all(&path).ok();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    #[test]
+    fn exec_argument_is_quoted() {
+        assert_eq!(
+            quote_exec_argument("/opt/FSRewire/client"),
+            "\"/opt/FSRewire/client\""
+        );
+        assert_eq!(
+            quote_exec_argument("/home/pilot/My Apps/client"),
+            "\"/home/pilot/My Apps/client\""
+        );
+    }
+
+    #[test]
+    fn exec_argument_escapes_are_doubled() {
+        assert_eq!(quote_exec_argument("a\"b"), "\"a\\\\\"b\"");
+        assert_eq!(quote_exec_argument("a`b"), "\"a\\\\`b\"");
+        assert_eq!(quote_exec_argument("$HOME"), "\"\\\\$HOME\"");
+        assert_eq!(quote_exec_argument("a\\b"), "\"a\\\\\\\\b\"");
+        assert_eq!(quote_exec_argument("100%"), "\"100%%\"");
+    }
+
+    #[test]
+    fn enable_writes_entry_and_disable_removes_it() {
+        let dir = TempDir::new("toggle");
+        let autostart = XdgAutostart::new(&dir.0, Path::new("/home/pilot/My Apps/client"));
+
+        assert!(!autostart.is_enabled());
+
+        autostart.set_enabled(true).unwrap();
+        let entry = fs::read_to_string(autostart.entry_path()).unwrap();
+
+        assert!(autostart.is_enabled());
+        assert!(entry.starts_with("[Desktop Entry]\n"));
+        assert!(entry
+            .lines()
+            .any(|line| line == "Exec=\"/home/pilot/My Apps/client\""));
+        assert_eq!(
+            autostart.entry_path(),
+            dir.0.join(format!("{}.desktop", APP_TITLE))
+        );
+
+        autostart.set_enabled(false).unwrap();
+
+        assert!(!autostart.is_enabled());
+        assert!(!autostart.entry_path().exists());
+
+        /* Disabling twice is fine */
+        autostart.set_enabled(false).unwrap();
+    }
+
+    #[test]
+    fn entry_hidden_by_the_desktop_is_disabled() {
+        let dir = TempDir::new("hidden");
+        let autostart = XdgAutostart::new(&dir.0, Path::new("/opt/FSRewire/client"));
+
+        autostart.set_enabled(true).unwrap();
+        let entry = fs::read_to_string(autostart.entry_path()).unwrap();
+        fs::write(autostart.entry_path(), format!("{}Hidden=true\n", entry)).unwrap();
+
+        assert!(!autostart.is_enabled());
+
+        fs::write(
+            autostart.entry_path(),
+            entry.replace(
+                "X-GNOME-Autostart-enabled=true",
+                "X-GNOME-Autostart-enabled=false",
+            ),
+        )
+        .unwrap();
+
+        assert!(!autostart.is_enabled());
+    }
+}
@@ -0,0 +1,175 @@
+use chrono::{DateTime, Local};
+use serde_derive::Serialize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    net::{IpAddr, SocketAddr},
+    sync::{mpsc, Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use winit::event_loop::EventLoopProxy;
+
+use crate::state::AppEvent;
+
+use super::{port::find_tcp_connections, relay::RelayRegistry};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientSource {
+    /// Connected through the built-in relay, bytes are counted.
+    Relay,
+    /// Connected to SimConnect directly, seen in the TCP table.
+    Direct,
+}
+
+/// A remote device connected to SimConnect.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConnectedClient {
+    pub peer: SocketAddr,
+    pub source: ClientSource,
+    /// Unix timestamp (seconds), for direct connections the time it was first seen.
+    pub connected_at: u64,
+    pub bytes_to_sim: Option<u64>,
+    pub bytes_to_peer: Option<u64>,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or(0)
+}
+
+/// Collects the relay peers and the remote ends of direct connections to `port`.
+/// Loopback connections are skipped, they are the relay itself or local add-ons.
+fn collect_clients(
+    relay: &RelayRegistry,
+    port: u16,
+    first_seen: &mut HashMap<SocketAddr, u64>,
+) -> Vec<ConnectedClient> {
+    let mut clients: Vec<ConnectedClient> = relay
+        .connections()
+        .into_iter()
+        .map(|connection| ConnectedClient {
+            peer: connection.peer,
+            source: ClientSource::Relay,
+            connected_at: connection.connected_at,
+            bytes_to_sim: Some(connection.bytes_to_sim),
+            bytes_to_peer: Some(connection.bytes_to_peer),
+        })
+        .collect();
+
+    let direct_peers: Vec<SocketAddr> = find_tcp_connections(port)
+        .into_iter()
+        .filter(|peer| !peer.ip().is_loopback())
+        .collect();
+
+    first_seen.retain(|peer, _| direct_peers.contains(peer));
+
+    clients.extend(direct_peers.into_iter().map(|peer| ConnectedClient {
+        peer,
+        source: ClientSource::Direct,
+        connected_at: *first_seen.entry(peer).or_insert_with(unix_now),
+        bytes_to_sim: None,
+        bytes_to_peer: None,
+    }));
+
+    clients
+}
+
+/// Refreshes `clients` every `interval` and reports addresses that were not connected before.
+/// A new SimConnect port can be sent through `retarget`.
+pub fn client_watch_thread(
+    proxy: EventLoopProxy<AppEvent>,
+    retarget: mpsc::Receiver<u16>,
+    mut port: u16,
+    relay: Arc<RelayRegistry>,
+    clients: Arc<Mutex<Vec<ConnectedClient>>>,
+    interval: Duration,
+) {
+    let mut first_seen = HashMap::new();
+    let mut known_addresses = BTreeSet::new();
+
+    loop {
+        let current = collect_clients(&relay, port, &mut first_seen);
+        let addresses: BTreeSet<IpAddr> = current.iter().map(|client| client.peer.ip()).collect();
+
+        let is_changed = clients
+            .lock()
+            .map(|mut clients| {
+                /* Byte counters too, so the window and the API follow relayed traffic */
+                let is_changed = *clients != current;
+                *clients = current;
+                is_changed
+            })
+            .unwrap_or(false);
+
+        if is_changed {
+            let new_addresses: Vec<IpAddr> =
+                addresses.difference(&known_addresses).copied().collect();
+
+            for address in &new_addresses {
+                log::info!("Client {} connected", address);
+            }
+            for address in known_addresses.difference(&addresses) {
+                log::info!("Client {} disconnected", address);
+            }
+
+            if proxy
+                .send_event(AppEvent::ClientsChanged(new_addresses))
+                .is_err()
+            {
+                break;
+            }
+        }
+
+        known_addresses = addresses;
+
+        match retarget.recv_timeout(interval) {
+            Ok(new_port) => port = new_port,
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1_048_575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// Summary for the status window with connect times and relayed bytes, empty without clients.
+pub fn describe_clients(clients: &[ConnectedClient]) -> String {
+    if clients.is_empty() {
+        return String::new();
+    }
+
+    let peers: Vec<String> = clients
+        .iter()
+        .map(|client| {
+            let connected_at =
+                DateTime::<Local>::from(UNIX_EPOCH + Duration::from_secs(client.connected_at))
+                    .format("%H:%M");
+
+            match (client.source, client.bytes_to_sim, client.bytes_to_peer) {
+                (ClientSource::Relay, Some(bytes_to_sim), Some(bytes_to_peer)) => format!(
+                    "{} since {} (relay, ↑{} ↓{})",
+                    client.peer.ip(),
+                    connected_at,
+                    format_bytes(bytes_to_sim),
+                    format_bytes(bytes_to_peer)
+                ),
+                (ClientSource::Relay, _, _) => {
+                    format!("{} since {} (relay)", client.peer.ip(), connected_at)
+                }
+                (ClientSource::Direct, _, _) => {
+                    format!("{} since {}", client.peer.ip(), connected_at)
+                }
+            }
+        })
+        .collect();
+
+    format!("Connected: {}", peers.join(", "))
+}
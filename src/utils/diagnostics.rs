@@ -0,0 +1,351 @@
+use chrono::Local;
+use std::{
+    fs::{self, File},
+    io::Write,
+    net::{IpAddr, Ipv4Addr},
+    path::{Path, PathBuf},
+};
+use zip::{write::FileOptions, ZipWriter};
+
+use crate::{
+    settings::{get_app_log_dir, Settings},
+    state::StateRecord,
+    APP_TITLE, RELESE_TAG,
+};
+
+use super::{
+    file::read_windows1252_file,
+    net::list_interfaces,
+    simconnect::{find_sim_installations, get_simconnect_backup_path},
+};
+
+static REDACTED: &str = "REDACTED";
+
+/// Default bundle location, the user's desktop (or home) with a timestamped name.
+pub fn get_default_diagnostics_path() -> PathBuf {
+    let dir = dirs::desktop_dir()
+        .or_else(dirs::home_dir)
+        .unwrap_or_default();
+
+    dir.join(format!(
+        "{}-diagnostics-{}.zip",
+        APP_TITLE,
+        Local::now().format("%Y%m%d-%H%M%S")
+    ))
+}
+
+/// Local addresses help troubleshooting, public ones identify the user and are hidden.
+fn is_local_address(address: &IpAddr) -> bool {
+    match address {
+        IpAddr::V4(address) => {
+            address.is_private()
+                || address.is_loopback()
+                || address.is_link_local()
+                || address.is_unspecified()
+        }
+        IpAddr::V6(address) => {
+            let first_segment = address.segments()[0];
+
+            address.is_loopback()
+                || address.is_unspecified()
+                || first_segment & 0xfe00 == 0xfc00
+                || first_segment & 0xffc0 == 0xfe80
+        }
+    }
+}
+
+fn redact_address(address: &IpAddr) -> String {
+    if is_local_address(address) {
+        address.to_string()
+    } else {
+        REDACTED.to_string()
+    }
+}
+
+/// Characters of IPv4 and IPv6 addresses, along with the port of `IPv4:port`.
+fn is_address_char(c: char) -> bool {
+    c.is_ascii_hexdigit() || c == '.' || c == ':'
+}
+
+fn redact_address_token(token: &str) -> String {
+    if let Ok(address) = token.parse::<IpAddr>() {
+        return redact_address(&address);
+    }
+
+    /* Sentences end in a dot or a colon, unicast targets are logged as `IPv4:port` */
+    let trimmed = token.trim_end_matches(['.', ':']);
+    let host = trimmed
+        .split_once(':')
+        .map(|(host, _)| host)
+        .filter(|host| host.parse::<Ipv4Addr>().is_ok())
+        .unwrap_or(trimmed);
+
+    match host.parse::<IpAddr>() {
+        Ok(address) => format!("{}{}", redact_address(&address), &token[host.len()..]),
+        Err(_) => token.to_string(),
+    }
+}
+
+/// Applies `redact_address` to the addresses in free text, like log lines.
+fn redact_addresses(content: &str) -> String {
+    let mut redacted = String::with_capacity(content.len());
+    let mut rest = content;
+
+    while let Some(start) = rest.find(is_address_char) {
+        redacted.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let length = rest.find(|c| !is_address_char(c)).unwrap_or(rest.len());
+        redacted.push_str(&redact_address_token(&rest[..length]));
+        rest = &rest[length..];
+    }
+
+    redacted.push_str(rest);
+    redacted
+}
+
+/// Hides the user name in paths, public addresses and the hosts of SimConnect sections.
+fn redact(content: &str) -> String {
+    let mut redacted = match dirs::home_dir().and_then(|home| home.to_str().map(str::to_string)) {
+        Some(home) => content.replace(&home, "%USERPROFILE%"),
+        None => content.to_string(),
+    };
+
+    let mut search_from = 0;
+
+    while let Some(start) = redacted[search_from..].find("<Address>") {
+        let value_start = search_from + start + "<Address>".len();
+        let Some(length) = redacted[value_start..].find("</Address>") else {
+            break;
+        };
+
+        let value = redacted[value_start..value_start + length].trim();
+        let is_local_value = value
+            .parse::<IpAddr>()
+            .map(|address| is_local_address(&address))
+            .unwrap_or(false);
+
+        if !is_local_value {
+            redacted.replace_range(value_start..value_start + length, REDACTED);
+            search_from = value_start + REDACTED.len();
+        } else {
+            search_from = value_start + length;
+        }
+    }
+
+    redact_addresses(&redacted)
+}
+
+fn redact_settings(settings: &Settings) -> Settings {
+    let mut settings = settings.clone();
+
+    if settings.api.token.is_some() {
+        settings.api.token = Some(REDACTED.to_string());
+    }
+
+    settings
+}
+
+fn describe_installations() -> String {
+    let installations = find_sim_installations();
+
+    if installations.is_empty() {
+        return "Unable to determine user home directory.\n".to_string();
+    }
+
+    installations
+        .iter()
+        .map(|installation| {
+            format!(
+                "{}: {} ({})\n",
+                installation.edition,
+                redact(&installation.xml_path),
+                if installation.is_present {
+                    "present"
+                } else {
+                    "missing"
+                }
+            )
+        })
+        .collect()
+}
+
+fn describe_interfaces() -> String {
+    match list_interfaces() {
+        Ok(interfaces) => interfaces
+            .iter()
+            .map(|interface| {
+                format!(
+                    "{} {} broadcast={} index={}\n",
+                    interface.name,
+                    redact_address(&interface.address),
+                    interface
+                        .broadcast
+                        .map(|broadcast| redact_address(&IpAddr::V4(broadcast)))
+                        .unwrap_or_else(|| "-".to_string()),
+                    interface
+                        .index
+                        .map(|index| index.to_string())
+                        .unwrap_or_else(|| "-".to_string())
+                )
+            })
+            .collect(),
+        Err(error) => format!("{}\n", error),
+    }
+}
+
+fn describe_history(history: Option<&[StateRecord]>) -> String {
+    match history {
+        Some(history) => history
+            .iter()
+            .map(|record| format!("{}\n", redact(&record.describe())))
+            .collect(),
+        None => "Not available, the bundle was created outside of a running client.\n".to_string(),
+    }
+}
+
+fn add_file(zip: &mut ZipWriter<File>, name: &str, content: &str) -> Result<(), String> {
+    zip.start_file(name, FileOptions::default())
+        .map_err(|e| format!("Error adding {} to the bundle: {}", name, e))?;
+    zip.write_all(content.as_bytes())
+        .map_err(|e| format!("Error writing {} to the bundle: {}", name, e))
+}
+
+/// Writes a zip with everything needed to investigate a discovery problem.
+/// `history` is `None` when no client is running in this process.
+pub fn export_diagnostics(
+    output: &Path,
+    settings: &Settings,
+    history: Option<&[StateRecord]>,
+) -> Result<(), String> {
+    let file =
+        File::create(output).map_err(|e| format!("Error creating diagnostics bundle: {}", e))?;
+    let mut zip = ZipWriter::new(file);
+
+    add_file(
+        &mut zip,
+        "version.txt",
+        &format!("{} {}\n", APP_TITLE, RELESE_TAG),
+    )?;
+    add_file(
+        &mut zip,
+        "settings.json",
+        &serde_json::to_string_pretty(&redact_settings(settings))
+            .map_err(|e| format!("Error serializing settings: {}", e))?,
+    )?;
+    add_file(&mut zip, "installations.txt", &describe_installations())?;
+    add_file(&mut zip, "interfaces.txt", &describe_interfaces())?;
+    add_file(&mut zip, "history.txt", &describe_history(history))?;
+
+    for installation in find_sim_installations()
+        .iter()
+        .filter(|installation| installation.is_present)
+    {
+        let backup_path = get_simconnect_backup_path(&installation.xml_path);
+
+        for (suffix, path) in [("", &installation.xml_path), (".bak", &backup_path)] {
+            if let Ok(content) = read_windows1252_file(path) {
+                let name = format!(
+                    "simconnect/{}-SimConnect.xml{}",
+                    installation.edition.replace(' ', ""),
+                    suffix
+                );
+                add_file(&mut zip, &name, &redact(&content))?;
+            }
+        }
+    }
+
+    if let Ok(entries) = get_app_log_dir().and_then(|dir| {
+        fs::read_dir(dir).map_err(|e| format!("Error reading log directory: {}", e))
+    }) {
+        for entry in entries.flatten() {
+            if let Ok(content) = fs::read_to_string(entry.path()) {
+                let name = format!("logs/{}", entry.file_name().to_string_lossy());
+                add_file(&mut zip, &name, &redact(&content))?;
+            }
+        }
+    }
+
+    zip.finish()
+        .map_err(|e| format!("Error finishing diagnostics bundle: {}", e))?;
+
+    log::info!("Diagnostics bundle written to {}", output.display());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_public_ipv4_addresses() {
+        assert_eq!(
+            redact("Sending to 8.8.8.8 and 192.168.1.10"),
+            "Sending to REDACTED and 192.168.1.10"
+        );
+        assert_eq!(
+            redact("Unicast target 203.0.113.5:500 failed: 10.0.0.2:500."),
+            "Unicast target REDACTED:500 failed: 10.0.0.2:500."
+        );
+        assert_eq!(redact("Client 1.1.1.1."), "Client REDACTED.");
+    }
+
+    #[test]
+    fn redacts_public_ipv6_addresses() {
+        assert_eq!(
+            redact("Announced on 2001:db8::1 and fe80::1%12"),
+            "Announced on REDACTED and fe80::1%12"
+        );
+        assert_eq!(
+            redact("Peer [2606:4700::1111]:500, local fd00::5:"),
+            "Peer [REDACTED]:500, local fd00::5:"
+        );
+        assert_eq!(redact("Bound to :: and ::1"), "Bound to :: and ::1");
+    }
+
+    #[test]
+    fn keeps_text_that_is_not_an_address() {
+        let content = "12:30:45 INFO Version 0.1.0 deadbeef cafe, 2024-01-01";
+
+        assert_eq!(redact(content), content);
+    }
+
+    #[test]
+    fn redacts_simconnect_addresses() {
+        let content = "<Address>8.8.8.8</Address><Address>sim.example.com</Address>\
+                       <Address>192.168.1.10</Address>";
+
+        assert_eq!(
+            redact(content),
+            "<Address>REDACTED</Address><Address>REDACTED</Address>\
+             <Address>192.168.1.10</Address>"
+        );
+    }
+
+    #[test]
+    fn redacts_the_home_path() {
+        let home = dirs::home_dir().unwrap();
+        let content = format!("Reading {}", home.join("SimConnect.xml").display());
+
+        assert_eq!(
+            redact(&content),
+            format!(
+                "Reading {}",
+                Path::new("%USERPROFILE%").join("SimConnect.xml").display()
+            )
+        );
+    }
+
+    #[test]
+    fn redacts_the_api_token() {
+        let mut settings = Settings::default();
+        assert_eq!(redact_settings(&settings).api.token, None);
+
+        settings.api.token = Some("secret".to_string());
+        let redacted = redact_settings(&settings);
+
+        assert_eq!(redacted.api.token.as_deref(), Some(REDACTED));
+        assert_eq!(redacted.api.bind, settings.api.bind);
+    }
+}
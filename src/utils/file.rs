@@ -4,7 +4,6 @@ use image::{load_from_memory, GenericImageView, ImageDecoder};
 use std::fs;
 use std::io::{Cursor, Read};
 use std::path::Path;
-use tray_icon::Icon as TryIcon;
 use winit::window::Icon as WindowIcon;
 
 pub fn read_windows1252_file(file_path: &str) -> Result<String, String> {
@@ -26,7 +25,7 @@ pub fn write_windows1252_file(file_path: &str, content: &str) -> Result<(), Stri
     }
 }
 
-fn load_png(png_icon_data: &'static [u8]) -> (Vec<u8>, u32, u32) {
+pub fn load_png(png_icon_data: &'static [u8]) -> (Vec<u8>, u32, u32) {
     let cursor = Cursor::new(png_icon_data);
     let dynamic_image = load_from_memory(&cursor.into_inner()).unwrap();
 
@@ -40,12 +39,37 @@ fn load_png(png_icon_data: &'static [u8]) -> (Vec<u8>, u32, u32) {
     (rgba_data, width, height)
 }
 
-pub fn load_try_icon(png_icon_data: &'static [u8]) -> TryIcon {
-    let (icon_rgba, icon_width, icon_height) = load_png(png_icon_data);
-    TryIcon::from_rgba(icon_rgba, icon_width, icon_height).expect("Failed to open try icon")
-}
-
 pub fn load_window_icon(png_icon_data: &'static [u8]) -> WindowIcon {
     let (icon_rgba, icon_width, icon_height) = load_png(png_icon_data);
     WindowIcon::from_rgba(icon_rgba, icon_width, icon_height).expect("Failed to open window icon")
 }
+
+/// Opens the system file manager with the file selected.
+/// Opens the file with the application associated with its type.
+pub fn open_in_default_app(path: &Path) -> Result<(), String> {
+    #[cfg(windows)]
+    let result = std::process::Command::new("explorer").arg(path).spawn();
+
+    #[cfg(not(windows))]
+    let result = std::process::Command::new("xdg-open").arg(path).spawn();
+
+    result
+        .map(|_| ())
+        .map_err(|e| format!("Error opening {}: {}", path.display(), e))
+}
+
+pub fn reveal_in_file_manager(path: &Path) -> Result<(), String> {
+    #[cfg(windows)]
+    let result = std::process::Command::new("explorer")
+        .arg(format!("/select,{}", path.display()))
+        .spawn();
+
+    #[cfg(not(windows))]
+    let result = std::process::Command::new("xdg-open")
+        .arg(path.parent().unwrap_or(path))
+        .spawn();
+
+    result
+        .map(|_| ())
+        .map_err(|e| format!("Error opening file manager: {}", e))
+}
@@ -0,0 +1,289 @@
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::hash_map::RandomState,
+    fs::{self, File, OpenOptions, TryLockError},
+    hash::{BuildHasher, Hasher},
+    io::{BufRead, BufReader, Read, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    path::PathBuf,
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+use winit::event_loop::EventLoopProxy;
+
+use crate::{settings::get_app_data_dir, state::AppEvent};
+
+static LOCK_FILE_NAME: &str = "instance.lock";
+/// Port and token of the running instance's IPC socket.
+static ENDPOINT_FILE_NAME: &str = "instance.json";
+static IPC_TIMEOUT: Duration = Duration::from_secs(5);
+/// Diagnostics of the running instance can take a while to write.
+static REPLY_TIMEOUT: Duration = Duration::from_secs(30);
+/// Requests and replies are a single short JSON line.
+static MAX_LINE_LENGTH: u64 = 64 * 1024;
+/// The running instance may still be starting its socket.
+static CONNECT_ATTEMPTS: u32 = 5;
+static CONNECT_RETRY_DELAY: Duration = Duration::from_millis(300);
+
+/// What a later launch asks the running instance to do.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum InstanceRequest {
+    /// Show the status window.
+    Show,
+    /// Write the diagnostics bundle, with the running instance's history.
+    Diagnostics { output: PathBuf },
+}
+
+#[derive(Serialize, Deserialize)]
+struct InstanceEndpoint {
+    port: u16,
+    token: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct InstanceMessage {
+    token: String,
+    request: InstanceRequest,
+}
+
+/// Held by the instance that owns the broadcaster and SimConnect.xml, released when the process ends.
+pub struct InstanceLock {
+    _file: File,
+}
+
+fn get_lock_path() -> Result<PathBuf, String> {
+    Ok(get_app_data_dir()?.join(LOCK_FILE_NAME))
+}
+
+fn get_endpoint_path() -> Result<PathBuf, String> {
+    Ok(get_app_data_dir()?.join(ENDPOINT_FILE_NAME))
+}
+
+/// Only processes of the same user can read the endpoint file, and with it the token.
+fn generate_token() -> String {
+    (0..2)
+        .map(|index| {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u32(std::process::id());
+            hasher.write_u32(index);
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+/// Takes the single-instance lock, `None` when another instance holds it.
+pub fn acquire_instance_lock() -> Result<Option<InstanceLock>, String> {
+    let lock_path = get_lock_path()?;
+
+    if let Some(dir) = lock_path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Error creating app data directory: {}", e))?;
+    }
+
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|e| format!("Error opening instance lock: {}", e))?;
+
+    match file.try_lock() {
+        Ok(_) => Ok(Some(InstanceLock { _file: file })),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(e)) => Err(format!("Error locking instance lock: {}", e)),
+    }
+}
+
+/// Reads one line of at most `MAX_LINE_LENGTH` bytes, waiting no longer than `timeout`.
+fn read_line(stream: &TcpStream, timeout: Duration, what: &str) -> Result<String, String> {
+    stream
+        .set_read_timeout(Some(timeout))
+        .map_err(|e| format!("Error setting the {} timeout: {}", what, e))?;
+
+    let mut line = String::new();
+
+    BufReader::new(stream.take(MAX_LINE_LENGTH))
+        .read_line(&mut line)
+        .map_err(|e| format!("Error reading {}: {}", what, e))?;
+
+    if !line.ends_with('\n') {
+        return Err(format!("Incomplete {}.", what));
+    }
+
+    Ok(line)
+}
+
+fn read_message(stream: &TcpStream) -> Result<InstanceMessage, String> {
+    let line = read_line(stream, IPC_TIMEOUT, "instance request")?;
+
+    serde_json::from_str(&line).map_err(|e| format!("Invalid instance request: {}", e))
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    token: &str,
+    proxy: &EventLoopProxy<AppEvent>,
+) -> Result<(), String> {
+    let reply = match read_message(&stream) {
+        Ok(message) if message.token == token => {
+            log::info!("Request from another instance: {:?}", message.request);
+
+            let (reply_sender, reply_receiver) = mpsc::channel();
+
+            match proxy.send_event(AppEvent::InstanceRequest(message.request, reply_sender)) {
+                Ok(_) => reply_receiver
+                    .recv_timeout(REPLY_TIMEOUT)
+                    .unwrap_or_else(|_| Err("The running instance didn't answer.".to_string())),
+                Err(_) => Err("The running instance is shutting down.".to_string()),
+            }
+        }
+        Ok(_) => Err("Invalid instance token.".to_string()),
+        Err(error) => Err(error),
+    };
+
+    let mut response =
+        serde_json::to_string(&reply).map_err(|e| format!("Error encoding reply: {}", e))?;
+    response.push('\n');
+
+    stream
+        .write_all(response.as_bytes())
+        .map_err(|e| format!("Error sending instance reply: {}", e))
+}
+
+fn instance_server_thread(listener: TcpListener, token: String, proxy: EventLoopProxy<AppEvent>) {
+    for stream in listener.incoming().flatten() {
+        if let Err(error) = handle_connection(stream, &token, &proxy) {
+            log::warn!("{}", error);
+        }
+    }
+}
+
+impl InstanceLock {
+    /// Opens the IPC socket on loopback and publishes it for later launches.
+    pub fn listen(&self, proxy: EventLoopProxy<AppEvent>) -> Result<(), String> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .map_err(|e| format!("Error opening instance socket: {}", e))?;
+        let port = listener
+            .local_addr()
+            .map_err(|e| format!("Error reading instance socket address: {}", e))?
+            .port();
+
+        let endpoint = InstanceEndpoint {
+            port,
+            token: generate_token(),
+        };
+
+        fs::write(
+            get_endpoint_path()?,
+            serde_json::to_string(&endpoint)
+                .map_err(|e| format!("Error encoding instance endpoint: {}", e))?,
+        )
+        .map_err(|e| format!("Error writing instance endpoint: {}", e))?;
+
+        log::debug!("Instance socket listening on port {}", port);
+
+        let token = endpoint.token;
+        thread::spawn(move || instance_server_thread(listener, token, proxy));
+
+        Ok(())
+    }
+}
+
+fn connect_to_running_instance() -> Result<(TcpStream, String), String> {
+    let mut last_error = String::new();
+
+    for attempt in 0..CONNECT_ATTEMPTS {
+        if attempt > 0 {
+            thread::sleep(CONNECT_RETRY_DELAY);
+        }
+
+        let endpoint = fs::read_to_string(get_endpoint_path()?)
+            .map_err(|e| format!("Error reading instance endpoint: {}", e))
+            .and_then(|content| {
+                serde_json::from_str::<InstanceEndpoint>(&content)
+                    .map_err(|e| format!("Invalid instance endpoint: {}", e))
+            });
+
+        let connection = endpoint.and_then(|endpoint| {
+            TcpStream::connect_timeout(
+                &SocketAddr::from((Ipv4Addr::LOCALHOST, endpoint.port)),
+                IPC_TIMEOUT,
+            )
+            .map(|stream| (stream, endpoint.token))
+            .map_err(|e| format!("Error connecting to the running instance: {}", e))
+        });
+
+        match connection {
+            Ok(connection) => return Ok(connection),
+            Err(error) => last_error = error,
+        }
+    }
+
+    Err(last_error)
+}
+
+/// Hands a request to the instance holding the lock and returns its answer.
+pub fn send_to_running_instance(request: InstanceRequest) -> Result<String, String> {
+    let (mut stream, token) = connect_to_running_instance()?;
+
+    let mut message = serde_json::to_string(&InstanceMessage { token, request })
+        .map_err(|e| format!("Error encoding instance request: {}", e))?;
+    message.push('\n');
+
+    stream
+        .write_all(message.as_bytes())
+        .map_err(|e| format!("Error sending instance request: {}", e))?;
+
+    let line = read_line(&stream, REPLY_TIMEOUT, "instance reply")?;
+
+    serde_json::from_str::<Result<String, String>>(&line)
+        .map_err(|e| format!("Invalid instance reply: {}", e))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A loopback connection, the accepted end first.
+    fn connection() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+
+        (server, client)
+    }
+
+    #[test]
+    fn reads_a_line() {
+        let (server, mut client) = connection();
+        client.write_all(b"{\"command\":\"show\"}\n").unwrap();
+
+        assert_eq!(
+            read_line(&server, IPC_TIMEOUT, "request").unwrap(),
+            "{\"command\":\"show\"}\n"
+        );
+    }
+
+    #[test]
+    fn long_lines_are_cut_off() {
+        let (server, mut client) = connection();
+
+        thread::spawn(move || {
+            let chunk = vec![b'x'; 1024];
+            while client.write_all(&chunk).is_ok() {}
+        });
+
+        assert_eq!(
+            read_line(&server, IPC_TIMEOUT, "request"),
+            Err("Incomplete request.".to_string())
+        );
+    }
+
+    #[test]
+    fn silent_clients_time_out() {
+        let (server, _client) = connection();
+
+        assert!(read_line(&server, Duration::from_millis(100), "request").is_err());
+    }
+}
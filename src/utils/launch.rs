@@ -0,0 +1,95 @@
+use std::{
+    process::Command,
+    thread,
+    time::{Duration, Instant},
+};
+use winit::event_loop::EventLoopProxy;
+
+use crate::{
+    settings::SimLauncher,
+    state::{AppEvent, SimLaunchPhase},
+};
+
+use super::msfs::check_if_msfs_running;
+
+static LAUNCH_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Opens a URI with the default handler, Steam and Store launches go through it.
+fn open_uri_command(uri: &str) -> Command {
+    #[cfg(windows)]
+    let mut command = Command::new("explorer");
+
+    #[cfg(not(windows))]
+    let mut command = Command::new("xdg-open");
+
+    command.arg(uri);
+    command
+}
+
+fn launch_command(launcher: &SimLauncher) -> Result<Command, String> {
+    match launcher {
+        SimLauncher::Off => Err("No simulator launcher is configured.".to_string()),
+        SimLauncher::Steam(app_id) => {
+            Ok(open_uri_command(&format!("steam://rungameid/{}", app_id)))
+        }
+        SimLauncher::Store(app_id) if cfg!(windows) => {
+            Ok(open_uri_command(&format!("shell:AppsFolder\\{}", app_id)))
+        }
+        SimLauncher::Store(_) => Err("Store apps can only be launched on Windows.".to_string()),
+        SimLauncher::Command(command_line) => {
+            #[cfg(windows)]
+            let mut command = Command::new("cmd");
+            #[cfg(windows)]
+            command.arg("/C").arg(command_line);
+
+            #[cfg(not(windows))]
+            let mut command = Command::new("sh");
+            #[cfg(not(windows))]
+            command.arg("-c").arg(command_line);
+
+            Ok(command)
+        }
+    }
+}
+
+/// Starts the simulator and waits for its SimConnect pipe, reporting the progress.
+/// Must only run after SimConnect.xml was written, the simulator reads it on startup.
+pub fn sim_launch_thread(
+    proxy: EventLoopProxy<AppEvent>,
+    launcher: SimLauncher,
+    timeout: Duration,
+) {
+    let report = |phase: SimLaunchPhase| proxy.send_event(AppEvent::SimLaunchChanged(phase)).ok();
+
+    log::info!("Launching the simulator with {:?}", launcher);
+
+    if let Err(error) = launch_command(&launcher).and_then(|mut command| {
+        command
+            .spawn()
+            .map_err(|e| format!("Error starting the simulator: {}", e))
+    }) {
+        log::error!("{}", error);
+        report(SimLaunchPhase::Failed(error));
+        return;
+    }
+
+    report(SimLaunchPhase::Launching);
+
+    let launched_at = Instant::now();
+
+    while !check_if_msfs_running() {
+        if launched_at.elapsed() > timeout {
+            log::warn!("Simulator didn't start within {}s", timeout.as_secs());
+            report(SimLaunchPhase::TimedOut);
+            return;
+        }
+
+        thread::sleep(LAUNCH_POLL_INTERVAL);
+    }
+
+    log::info!(
+        "Simulator started after {}s",
+        launched_at.elapsed().as_secs()
+    );
+    report(SimLaunchPhase::Started);
+}
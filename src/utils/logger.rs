@@ -0,0 +1,163 @@
+use chrono::Local;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+};
+
+use crate::settings::get_app_log_dir;
+
+static LOG_FILE_NAME: &str = "client.log";
+/// A log file is rotated once it grows past this size.
+static LOG_FILE_MAX_SIZE: u64 = 1024 * 1024;
+/// Number of rotated files kept next to the current one (`client.1.log`, `client.2.log`, ...).
+static LOG_FILE_ROTATIONS: usize = 3;
+/// Number of entries kept in memory for the log viewer.
+static RECENT_ENTRIES_LIMIT: usize = 200;
+
+static LOGGER: OnceLock<FileLogger> = OnceLock::new();
+
+struct LogFile {
+    dir: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(dir: PathBuf) -> Result<Self, String> {
+        fs::create_dir_all(&dir).map_err(|e| format!("Error creating log directory: {}", e))?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(LOG_FILE_NAME))
+            .map_err(|e| format!("Error opening log file: {}", e))?;
+        let size = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+
+        Ok(LogFile { dir, file, size })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        self.dir.join(format!("client.{}.log", index))
+    }
+
+    fn rotate(&mut self) -> Result<(), String> {
+        for index in (1..LOG_FILE_ROTATIONS).rev() {
+            let from = self.rotated_path(index);
+            if from.exists() {
+                fs::rename(&from, self.rotated_path(index + 1)).ok();
+            }
+        }
+
+        fs::rename(self.dir.join(LOG_FILE_NAME), self.rotated_path(1))
+            .map_err(|e| format!("Error rotating log file: {}", e))?;
+
+        *self = LogFile::open(self.dir.clone())?;
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.size + line.len() as u64 > LOG_FILE_MAX_SIZE && self.rotate().is_err() {
+            return;
+        }
+
+        if writeln!(self.file, "{}", line).is_ok() {
+            self.size += line.len() as u64 + 1;
+        }
+    }
+}
+
+struct FileLogger {
+    level: LevelFilter,
+    file: Mutex<Option<LogFile>>,
+    recent: Mutex<VecDeque<String>>,
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        /* Dependencies (wgpu, winit, ...) are only interesting when something goes wrong */
+        if metadata.target().starts_with(env!("CARGO_CRATE_NAME")) {
+            metadata.level() <= self.level
+        } else {
+            metadata.level() <= Level::Warn
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!(
+            "{} {:<5} [{}] {}",
+            Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        #[cfg(debug_assertions)]
+        eprintln!("{}", line);
+
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(file) = file.as_mut() {
+                file.write_line(&line);
+            }
+        }
+
+        if let Ok(mut recent) = self.recent.lock() {
+            if recent.len() == RECENT_ENTRIES_LIMIT {
+                recent.pop_front();
+            }
+            recent.push_back(line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(file) = file.as_mut() {
+                file.file.flush().ok();
+            }
+        }
+    }
+}
+
+/// Installs the global logger writing to the rotated file in the user's data directory.
+/// Entries are still kept in memory for the viewer when the file cannot be opened.
+pub fn init_logger(level: LevelFilter) {
+    let file = get_app_log_dir().and_then(LogFile::open);
+    let file_error = file.as_ref().err().cloned();
+
+    let logger = LOGGER.get_or_init(|| FileLogger {
+        level,
+        file: Mutex::new(file.ok()),
+        recent: Mutex::new(VecDeque::with_capacity(RECENT_ENTRIES_LIMIT)),
+    });
+
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(level.max(LevelFilter::Warn));
+    }
+
+    if let Some(error) = file_error {
+        log::warn!("File logging disabled: {}", error);
+    }
+}
+
+/// The newest `count` log entries, oldest first.
+pub fn get_recent_log_entries(count: usize) -> Vec<String> {
+    let Some(logger) = LOGGER.get() else {
+        return Vec::new();
+    };
+
+    match logger.recent.lock() {
+        Ok(recent) => recent
+            .iter()
+            .skip(recent.len().saturating_sub(count))
+            .cloned()
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
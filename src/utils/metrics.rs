@@ -0,0 +1,165 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+#[derive(Default)]
+struct InterfaceCounters {
+    packets_sent: u64,
+    send_errors: u64,
+}
+
+/// Process wide counters and gauges, rendered in the Prometheus text format.
+pub struct Metrics {
+    started_at: Instant,
+    /// Keyed by address family and interface (IPv4 address or IPv6 scope id).
+    interfaces: Mutex<BTreeMap<(&'static str, String), InterfaceCounters>>,
+    sim_running: AtomicBool,
+    /// Probe latency in microseconds, `u64::MAX` when the endpoint is not accepting connections.
+    probe_latency_us: AtomicU64,
+    config_rewrites: AtomicU64,
+}
+
+/// Starts the uptime clock, later calls of `metrics` reuse the same instance.
+pub fn init_metrics() {
+    metrics();
+}
+
+pub fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(|| Metrics {
+        started_at: Instant::now(),
+        interfaces: Mutex::new(BTreeMap::new()),
+        sim_running: AtomicBool::new(false),
+        probe_latency_us: AtomicU64::new(u64::MAX),
+        config_rewrites: AtomicU64::new(0),
+    })
+}
+
+impl Metrics {
+    fn update_interface(
+        &self,
+        family: &'static str,
+        interface: String,
+        update: impl FnOnce(&mut InterfaceCounters),
+    ) {
+        if let Ok(mut interfaces) = self.interfaces.lock() {
+            update(interfaces.entry((family, interface)).or_default());
+        }
+    }
+
+    pub fn record_packet_sent(&self, family: &'static str, interface: String) {
+        self.update_interface(family, interface, |counters| counters.packets_sent += 1);
+    }
+
+    pub fn record_send_error(&self, family: &'static str, interface: String) {
+        self.update_interface(family, interface, |counters| counters.send_errors += 1);
+    }
+
+    pub fn set_sim_running(&self, is_running: bool) {
+        self.sim_running.store(is_running, Ordering::Relaxed);
+    }
+
+    pub fn set_probe_latency(&self, latency: Option<Duration>) {
+        let latency_us = latency
+            .map(|latency| latency.as_micros() as u64)
+            .unwrap_or(u64::MAX);
+        self.probe_latency_us.store(latency_us, Ordering::Relaxed);
+    }
+
+    pub fn record_config_rewrite(&self) {
+        self.config_rewrites.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+
+        writeln!(
+            output,
+            "# HELP fsrewire_packets_broadcast_total Discovery packets sent.\n\
+             # TYPE fsrewire_packets_broadcast_total counter"
+        )
+        .ok();
+        let interfaces = self.interfaces.lock();
+        if let Ok(interfaces) = &interfaces {
+            for ((family, interface), counters) in interfaces.iter() {
+                writeln!(
+                    output,
+                    "fsrewire_packets_broadcast_total{{family=\"{}\",interface=\"{}\"}} {}",
+                    family, interface, counters.packets_sent
+                )
+                .ok();
+            }
+        }
+
+        writeln!(
+            output,
+            "# HELP fsrewire_send_errors_total Discovery packets that failed to send.\n\
+             # TYPE fsrewire_send_errors_total counter"
+        )
+        .ok();
+        if let Ok(interfaces) = &interfaces {
+            for ((family, interface), counters) in interfaces.iter() {
+                writeln!(
+                    output,
+                    "fsrewire_send_errors_total{{family=\"{}\",interface=\"{}\"}} {}",
+                    family, interface, counters.send_errors
+                )
+                .ok();
+            }
+        }
+        drop(interfaces);
+
+        writeln!(
+            output,
+            "# HELP fsrewire_sim_running Whether the simulator's SimConnect pipe is open.\n\
+             # TYPE fsrewire_sim_running gauge\n\
+             fsrewire_sim_running {}",
+            self.sim_running.load(Ordering::Relaxed) as u8
+        )
+        .ok();
+
+        /* No sample is exported while the endpoint is not accepting connections */
+        let probe_latency_us = self.probe_latency_us.load(Ordering::Relaxed);
+        writeln!(
+            output,
+            "# HELP fsrewire_probe_latency_seconds Connect time of the last endpoint probe.\n\
+             # TYPE fsrewire_probe_latency_seconds gauge"
+        )
+        .ok();
+        if probe_latency_us != u64::MAX {
+            writeln!(
+                output,
+                "fsrewire_probe_latency_seconds {}",
+                probe_latency_us as f64 / 1_000_000.0
+            )
+            .ok();
+        }
+
+        writeln!(
+            output,
+            "# HELP fsrewire_config_rewrites_total Changes written to SimConnect.xml.\n\
+             # TYPE fsrewire_config_rewrites_total counter\n\
+             fsrewire_config_rewrites_total {}",
+            self.config_rewrites.load(Ordering::Relaxed)
+        )
+        .ok();
+
+        writeln!(
+            output,
+            "# HELP fsrewire_uptime_seconds Time since the client started.\n\
+             # TYPE fsrewire_uptime_seconds gauge\n\
+             fsrewire_uptime_seconds {}",
+            self.started_at.elapsed().as_secs()
+        )
+        .ok();
+
+        output
+    }
+}
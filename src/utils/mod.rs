@@ -1,5 +1,18 @@
+pub mod autostart;
+pub mod clients;
+pub mod diagnostics;
 pub mod file;
+pub mod instance;
+pub mod launch;
+pub mod logger;
+pub mod metrics;
 pub mod msfs;
+pub mod net;
+pub mod packet;
+pub mod port;
+pub mod probe;
+pub mod reflector;
+pub mod relay;
 pub mod simconnect;
 pub mod udp;
 pub mod wgpu;
@@ -1,6 +1,100 @@
-use std::fs;
+use std::{fs, thread, time::Duration};
+use winit::event_loop::EventLoopProxy;
+
+use crate::state::AppEvent;
+
+use super::metrics::metrics;
+
+/* Executable names of MSFS 2020 and MSFS 2024 */
+static MSFS_PROCESS_NAMES: [&str; 2] = ["FlightSimulator.exe", "FlightSimulator2024.exe"];
 
 pub fn check_if_msfs_running() -> bool {
     /* Opened SimConnect pipe indicates that MSFS2020 is running */
     fs::metadata("\\\\.\\pipe\\Microsoft Flight Simulator\\SimConnect").is_ok()
 }
+
+/// Reports the simulator starting and stopping, the first check is always reported.
+pub fn msfs_watch_thread(proxy: EventLoopProxy<AppEvent>, poll_interval: Duration) {
+    let mut was_running = None;
+
+    loop {
+        let is_running = check_if_msfs_running();
+
+        if was_running != Some(is_running) {
+            was_running = Some(is_running);
+            metrics().set_sim_running(is_running);
+
+            log::info!(
+                "Simulator {}",
+                if is_running {
+                    "is running"
+                } else {
+                    "is not running"
+                }
+            );
+
+            if proxy
+                .send_event(AppEvent::SimPresenceChanged(is_running))
+                .is_err()
+            {
+                break;
+            }
+        }
+
+        thread::sleep(poll_interval);
+    }
+}
+
+#[cfg(windows)]
+pub fn find_msfs_process_ids() -> Vec<u32> {
+    use std::mem::size_of;
+    use windows_sys::Win32::{
+        Foundation::{CloseHandle, INVALID_HANDLE_VALUE},
+        System::Diagnostics::ToolHelp::{
+            CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
+            TH32CS_SNAPPROCESS,
+        },
+    };
+
+    let mut process_ids = Vec::new();
+
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+
+        if snapshot == INVALID_HANDLE_VALUE {
+            return process_ids;
+        }
+
+        let mut entry: PROCESSENTRY32W = std::mem::zeroed();
+        entry.dwSize = size_of::<PROCESSENTRY32W>() as u32;
+
+        let mut has_entry = Process32FirstW(snapshot, &mut entry) != 0;
+
+        while has_entry {
+            let name_length = entry
+                .szExeFile
+                .iter()
+                .position(|c| *c == 0)
+                .unwrap_or(entry.szExeFile.len());
+            let name = String::from_utf16_lossy(&entry.szExeFile[..name_length]);
+
+            if MSFS_PROCESS_NAMES
+                .iter()
+                .any(|msfs_name| name.eq_ignore_ascii_case(msfs_name))
+            {
+                process_ids.push(entry.th32ProcessID);
+            }
+
+            has_entry = Process32NextW(snapshot, &mut entry) != 0;
+        }
+
+        CloseHandle(snapshot);
+    }
+
+    process_ids
+}
+
+#[cfg(not(windows))]
+pub fn find_msfs_process_ids() -> Vec<u32> {
+    Vec::new()
+}
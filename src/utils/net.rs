@@ -0,0 +1,414 @@
+use if_addrs::{get_if_addrs, IfAddr};
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    str::FromStr,
+    thread,
+    time::Duration,
+};
+use winit::event_loop::EventLoopProxy;
+
+use crate::{settings::Exposure, state::AppEvent};
+
+pub static ANY_IPV4_ADDR: Ipv4Addr = Ipv4Addr::UNSPECIFIED;
+
+/// Address SimConnect is bound to and the broadcast address its announcement is sent to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExposedInterface {
+    pub address: Ipv4Addr,
+    pub broadcast: Ipv4Addr,
+}
+
+impl ExposedInterface {
+    pub fn all() -> Self {
+        ExposedInterface {
+            address: ANY_IPV4_ADDR,
+            broadcast: Ipv4Addr::BROADCAST,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ipv4Cidr {
+    pub network: Ipv4Addr,
+    pub prefix: u8,
+}
+
+impl Ipv4Cidr {
+    fn mask(&self) -> u32 {
+        if self.prefix == 0 {
+            0
+        } else {
+            u32::MAX << (32 - self.prefix)
+        }
+    }
+
+    pub fn contains(&self, address: &Ipv4Addr) -> bool {
+        u32::from(*address) & self.mask() == u32::from(self.network) & self.mask()
+    }
+}
+
+impl FromStr for Ipv4Cidr {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) = match value.trim().split_once('/') {
+            Some((address, prefix)) => (address, prefix),
+            None => (value.trim(), "32"),
+        };
+
+        let network = address
+            .parse::<Ipv4Addr>()
+            .map_err(|e| format!("Invalid CIDR address '{}': {}", value, e))?;
+        let prefix = prefix
+            .parse::<u8>()
+            .ok()
+            .filter(|prefix| *prefix <= 32)
+            .ok_or_else(|| format!("Invalid CIDR prefix in '{}'", value))?;
+
+        Ok(Ipv4Cidr { network, prefix })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NetInterface {
+    pub name: String,
+    pub address: IpAddr,
+    pub broadcast: Option<Ipv4Addr>,
+    pub netmask: Option<Ipv4Addr>,
+    pub index: Option<u32>,
+}
+
+impl NetInterface {
+    /// Whether the IPv4 address is in the interface's subnet.
+    pub fn contains(&self, address: &Ipv4Addr) -> bool {
+        match (self.address, self.netmask) {
+            (IpAddr::V4(own), Some(netmask)) => {
+                u32::from(own) & u32::from(netmask) == u32::from(*address) & u32::from(netmask)
+            }
+            _ => false,
+        }
+    }
+}
+
+pub fn list_interfaces() -> Result<Vec<NetInterface>, String> {
+    let interfaces =
+        get_if_addrs().map_err(|e| format!("Error listing network interfaces: {}", e))?;
+
+    Ok(interfaces
+        .into_iter()
+        .map(|interface| {
+            let (broadcast, netmask) = match &interface.addr {
+                /* Windows does not report it, so derive it from the netmask */
+                IfAddr::V4(addr) => (
+                    Some(addr.broadcast.unwrap_or_else(|| {
+                        Ipv4Addr::from(u32::from(addr.ip) | !u32::from(addr.netmask))
+                    })),
+                    Some(addr.netmask),
+                ),
+                IfAddr::V6(_) => (None, None),
+            };
+
+            NetInterface {
+                name: interface.name.clone(),
+                address: interface.ip(),
+                broadcast,
+                netmask,
+                index: interface.index,
+            }
+        })
+        .collect())
+}
+
+fn find_interface(
+    interfaces: &[NetInterface],
+    predicate: impl Fn(&NetInterface, &Ipv4Addr) -> bool,
+) -> Option<ExposedInterface> {
+    interfaces
+        .iter()
+        .find_map(|interface| match interface.address {
+            IpAddr::V4(address) if predicate(interface, &address) => Some(ExposedInterface {
+                address,
+                broadcast: interface.broadcast.unwrap_or(Ipv4Addr::BROADCAST),
+            }),
+            _ => None,
+        })
+}
+
+fn select_interface(
+    interfaces: &[NetInterface],
+    exposure: &Exposure,
+) -> Result<ExposedInterface, String> {
+    match exposure {
+        Exposure::All => Ok(ExposedInterface::all()),
+        Exposure::Interface(name) => find_interface(interfaces, |interface, address| {
+            interface.name == *name || address.to_string() == *name
+        })
+        .ok_or_else(|| format!("Interface '{}' has no IPv4 address.", name)),
+        Exposure::Subnet(cidr) => {
+            let cidr = cidr.parse::<Ipv4Cidr>()?;
+
+            find_interface(interfaces, |_, address| cidr.contains(address))
+                .ok_or_else(|| format!("No interface found in subnet {}.", cidr.network))
+        }
+    }
+}
+
+pub fn resolve_exposure(exposure: &Exposure) -> Result<ExposedInterface, String> {
+    match exposure {
+        Exposure::All => Ok(ExposedInterface::all()),
+        _ => select_interface(&list_interfaces()?, exposure),
+    }
+}
+
+/// Address remote devices reach the client on, the first private IPv4 one when exposed on all interfaces.
+pub fn reachable_address(interface: &ExposedInterface) -> Ipv4Addr {
+    if !interface.address.is_unspecified() {
+        return interface.address;
+    }
+
+    list_interfaces()
+        .unwrap_or_default()
+        .iter()
+        .find_map(|interface| match interface.address {
+            IpAddr::V4(address) if address.is_private() => Some(address),
+            _ => None,
+        })
+        .unwrap_or(interface.address)
+}
+
+/// Interface indexes used as scope ids for IPv6 link-local multicast,
+/// limited to the adapters that carry the exposed IPv4 address.
+pub fn ipv6_scope_ids(exposure: &Exposure) -> Result<Vec<u32>, String> {
+    let interfaces = list_interfaces()?;
+    let exposed = resolve_exposure(exposure)?;
+
+    let exposed_names: Vec<&String> = interfaces
+        .iter()
+        .filter(|interface| {
+            *exposure == Exposure::All || interface.address == IpAddr::V4(exposed.address)
+        })
+        .map(|interface| &interface.name)
+        .collect();
+
+    let mut scope_ids: Vec<u32> = interfaces
+        .iter()
+        .filter(|interface| interface.address.is_ipv6() && !interface.address.is_loopback())
+        .filter(|interface| exposed_names.contains(&&interface.name))
+        .filter_map(|interface| interface.index)
+        .collect();
+
+    scope_ids.sort_unstable();
+    scope_ids.dedup();
+
+    Ok(scope_ids)
+}
+
+/// Polls the exposed interface and reports when its address changes (e.g. a new DHCP lease),
+/// or when it gets one at all after `current` couldn't be resolved.
+pub fn interface_watch_thread(
+    proxy: EventLoopProxy<AppEvent>,
+    exposure: Exposure,
+    mut current: Option<ExposedInterface>,
+    poll_interval: Duration,
+) {
+    if exposure == Exposure::All {
+        return;
+    }
+
+    let mut is_resolved = current.is_some();
+
+    loop {
+        thread::sleep(poll_interval);
+
+        match resolve_exposure(&exposure) {
+            Ok(resolved) => {
+                is_resolved = true;
+
+                if current != Some(resolved) {
+                    match current {
+                        Some(current) => log::info!(
+                            "Exposed interface address changed from {} to {}",
+                            current.address,
+                            resolved.address
+                        ),
+                        None => {
+                            log::info!("Exposed interface now has address {}", resolved.address)
+                        }
+                    }
+                    current = Some(resolved);
+
+                    if proxy
+                        .send_event(AppEvent::ExposedInterfaceChanged(resolved))
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            }
+            Err(error) if is_resolved => {
+                is_resolved = false;
+                log::warn!("{}", error);
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(value: &str) -> Ipv4Addr {
+        value.parse().unwrap()
+    }
+
+    fn cidr(value: &str) -> Ipv4Cidr {
+        value.parse().unwrap()
+    }
+
+    fn interface(name: &str, ip: &str, broadcast: Option<&str>) -> NetInterface {
+        NetInterface {
+            name: name.to_string(),
+            address: ip.parse().unwrap(),
+            broadcast: broadcast.map(address),
+            netmask: None,
+            index: None,
+        }
+    }
+
+    fn interfaces() -> Vec<NetInterface> {
+        vec![
+            interface("Loopback", "127.0.0.1", Some("127.255.255.255")),
+            interface("Ethernet", "fe80::1", None),
+            interface("Ethernet", "192.168.1.10", Some("192.168.1.255")),
+            interface("Wi-Fi", "10.0.0.5", None),
+        ]
+    }
+
+    #[test]
+    fn interface_subnet() {
+        let interface = NetInterface {
+            name: "Ethernet".to_string(),
+            address: IpAddr::V4(address("192.168.1.10")),
+            broadcast: Some(address("192.168.1.255")),
+            netmask: Some(address("255.255.255.0")),
+            index: Some(1),
+        };
+
+        assert!(interface.contains(&address("192.168.1.200")));
+        assert!(!interface.contains(&address("192.168.2.200")));
+    }
+
+    #[test]
+    fn parses_cidr() {
+        assert_eq!(
+            cidr("192.168.1.0/24"),
+            Ipv4Cidr {
+                network: address("192.168.1.0"),
+                prefix: 24
+            }
+        );
+        assert_eq!(cidr(" 10.0.0.0/8 ").prefix, 8);
+        assert_eq!(cidr("10.0.0.5").prefix, 32);
+    }
+
+    #[test]
+    fn rejects_invalid_cidr() {
+        for value in [
+            "",
+            "/24",
+            "192.168.1.0/",
+            "192.168.1.0/33",
+            "192.168.1/24",
+            "fe80::/64",
+            "192.168.1.0/-1",
+        ] {
+            assert!(value.parse::<Ipv4Cidr>().is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn contains_addresses_in_the_network() {
+        let network = cidr("192.168.1.0/24");
+
+        assert!(network.contains(&address("192.168.1.0")));
+        assert!(network.contains(&address("192.168.1.255")));
+        assert!(!network.contains(&address("192.168.0.255")));
+        assert!(!network.contains(&address("192.168.2.0")));
+    }
+
+    #[test]
+    fn host_bits_of_the_network_are_ignored() {
+        assert!(cidr("192.168.1.77/24").contains(&address("192.168.1.3")));
+    }
+
+    #[test]
+    fn prefix_edges() {
+        assert!(cidr("0.0.0.0/0").contains(&address("255.255.255.255")));
+        assert!(cidr("203.0.113.9/0").contains(&address("1.2.3.4")));
+
+        assert!(cidr("10.0.0.5/32").contains(&address("10.0.0.5")));
+        assert!(!cidr("10.0.0.5/32").contains(&address("10.0.0.4")));
+
+        assert!(cidr("172.16.0.0/12").contains(&address("172.31.255.255")));
+        assert!(!cidr("172.16.0.0/12").contains(&address("172.32.0.0")));
+
+        assert!(cidr("128.0.0.0/1").contains(&address("200.1.1.1")));
+        assert!(!cidr("128.0.0.0/1").contains(&address("127.255.255.255")));
+    }
+
+    #[test]
+    fn selects_interface_by_name_or_address() {
+        let selected =
+            select_interface(&interfaces(), &Exposure::Interface("Ethernet".to_string()));
+
+        assert_eq!(
+            selected,
+            Ok(ExposedInterface {
+                address: address("192.168.1.10"),
+                broadcast: address("192.168.1.255"),
+            })
+        );
+        assert_eq!(
+            select_interface(
+                &interfaces(),
+                &Exposure::Interface("192.168.1.10".to_string())
+            ),
+            selected
+        );
+    }
+
+    #[test]
+    fn selects_interface_by_subnet() {
+        assert_eq!(
+            select_interface(&interfaces(), &Exposure::Subnet("10.0.0.0/8".to_string())),
+            Ok(ExposedInterface {
+                address: address("10.0.0.5"),
+                broadcast: Ipv4Addr::BROADCAST,
+            })
+        );
+    }
+
+    #[test]
+    fn missing_interface_is_an_error() {
+        assert!(
+            select_interface(&interfaces(), &Exposure::Interface("Bluetooth".to_string())).is_err()
+        );
+        assert!(select_interface(
+            &interfaces(),
+            &Exposure::Subnet("172.16.0.0/12".to_string())
+        )
+        .is_err());
+        assert!(
+            select_interface(&interfaces(), &Exposure::Subnet("not-a-subnet".to_string())).is_err()
+        );
+    }
+
+    #[test]
+    fn all_interfaces_use_the_wildcard_address() {
+        assert_eq!(
+            select_interface(&[], &Exposure::All),
+            Ok(ExposedInterface::all())
+        );
+    }
+}
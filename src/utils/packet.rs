@@ -0,0 +1,257 @@
+use std::net::IpAddr;
+
+/// Prefix of the original announcement, `FSR_SMC:{SimConnectPort}`.
+pub static LEGACY_PACKET_PREFIX: &str = "FSR_SMC";
+/// Prefix of the versioned announcement, `FSR_SMC2;key=value;...`.
+pub static VERSIONED_PACKET_PREFIX: &str = "FSR_SMC2";
+/// Prefix of the withdrawal, `FSR_SMC_BYE:{SimConnectPort}`, sent when SimConnect goes away.
+/// Reflected withdrawals use the versioned fields, `FSR_SMC_BYE;key=value;...`.
+pub static WITHDRAWAL_PACKET_PREFIX: &str = "FSR_SMC_BYE";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AddressFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl AddressFamily {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AddressFamily::Ipv4 => "4",
+            AddressFamily::Ipv6 => "6",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "4" => Some(AddressFamily::Ipv4),
+            "6" => Some(AddressFamily::Ipv6),
+            _ => None,
+        }
+    }
+}
+
+fn is_port(value: &str) -> bool {
+    value.parse::<u16>().is_ok()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Announcement {
+    pub family: AddressFamily,
+    pub port: String,
+    /// Address SimConnect listens on, when it differs from the datagram source.
+    pub address: Option<IpAddr>,
+    /// Instance IDs of the reflectors that forwarded the announcement, in order.
+    pub via: Vec<String>,
+    /// The host stopped announcing the endpoint.
+    pub is_withdrawal: bool,
+}
+
+impl Announcement {
+    pub fn encode_legacy(&self) -> String {
+        format!("{}:{}", LEGACY_PACKET_PREFIX, self.port)
+    }
+
+    pub fn encode_withdrawal(&self) -> String {
+        format!("{}:{}", WITHDRAWAL_PACKET_PREFIX, self.port)
+    }
+
+    pub fn encode_versioned(&self) -> String {
+        let prefix = if self.is_withdrawal {
+            WITHDRAWAL_PACKET_PREFIX
+        } else {
+            VERSIONED_PACKET_PREFIX
+        };
+        let mut packet = format!("{};af={};port={}", prefix, self.family.as_str(), self.port);
+
+        if let Some(address) = &self.address {
+            packet.push_str(&format!(";addr={}", address));
+        }
+
+        if !self.via.is_empty() {
+            packet.push_str(&format!(";via={}", self.via.join(",")));
+        }
+
+        packet
+    }
+
+    /// Parses announcements and withdrawals, in both the legacy and the versioned format.
+    pub fn parse(packet: &str) -> Option<Self> {
+        let versioned = packet
+            .strip_prefix(&format!("{};", VERSIONED_PACKET_PREFIX))
+            .map(|fields| (fields, false))
+            .or_else(|| {
+                packet
+                    .strip_prefix(&format!("{};", WITHDRAWAL_PACKET_PREFIX))
+                    .map(|fields| (fields, true))
+            });
+
+        if let Some((fields, is_withdrawal)) = versioned {
+            let mut family = None;
+            let mut port = None;
+            let mut address = None;
+            let mut via = Vec::new();
+
+            for field in fields.split(';') {
+                match field.split_once('=') {
+                    Some(("af", value)) => family = AddressFamily::parse(value),
+                    Some(("port", value)) => port = Some(value.to_string()),
+                    Some(("addr", value)) => address = value.parse().ok(),
+                    Some(("via", value)) => via = value.split(',').map(str::to_string).collect(),
+                    /* Unknown keys are skipped so newer packets stay readable */
+                    _ => {}
+                }
+            }
+
+            return Some(Announcement {
+                family: family?,
+                port: port.filter(|port| is_port(port))?,
+                address,
+                via,
+                is_withdrawal,
+            });
+        }
+
+        let legacy = packet
+            .strip_prefix(&format!("{}:", LEGACY_PACKET_PREFIX))
+            .map(|port| (port, false))
+            .or_else(|| {
+                packet
+                    .strip_prefix(&format!("{}:", WITHDRAWAL_PACKET_PREFIX))
+                    .map(|port| (port, true))
+            });
+
+        legacy
+            .filter(|(port, _)| is_port(port))
+            .map(|(port, is_withdrawal)| Announcement {
+                family: AddressFamily::Ipv4,
+                port: port.to_string(),
+                address: None,
+                via: Vec::new(),
+                is_withdrawal,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn announcement(family: AddressFamily, port: &str) -> Announcement {
+        Announcement {
+            family,
+            port: port.to_string(),
+            address: None,
+            via: Vec::new(),
+            is_withdrawal: false,
+        }
+    }
+
+    #[test]
+    fn legacy_round_trip() {
+        let legacy = announcement(AddressFamily::Ipv4, "500");
+
+        assert_eq!(legacy.encode_legacy(), "FSR_SMC:500");
+        assert_eq!(Announcement::parse("FSR_SMC:500"), Some(legacy));
+    }
+
+    #[test]
+    fn versioned_round_trip() {
+        let versioned = Announcement {
+            address: Some("fe80::1".parse().unwrap()),
+            via: vec!["reflector-a".to_string(), "reflector-b".to_string()],
+            ..announcement(AddressFamily::Ipv6, "501")
+        };
+        let packet = versioned.encode_versioned();
+
+        assert_eq!(
+            packet,
+            "FSR_SMC2;af=6;port=501;addr=fe80::1;via=reflector-a,reflector-b"
+        );
+        assert_eq!(Announcement::parse(&packet), Some(versioned));
+    }
+
+    #[test]
+    fn versioned_minimal_round_trip() {
+        let versioned = announcement(AddressFamily::Ipv4, "500");
+        let packet = versioned.encode_versioned();
+
+        assert_eq!(packet, "FSR_SMC2;af=4;port=500");
+        assert_eq!(Announcement::parse(&packet), Some(versioned));
+    }
+
+    #[test]
+    fn versioned_fields_in_any_order_and_unknown_keys() {
+        assert_eq!(
+            Announcement::parse("FSR_SMC2;future=1;port=500;af=4;addr=192.168.1.10"),
+            Some(Announcement {
+                address: Some("192.168.1.10".parse().unwrap()),
+                ..announcement(AddressFamily::Ipv4, "500")
+            })
+        );
+    }
+
+    #[test]
+    fn legacy_withdrawal_round_trip() {
+        let withdrawal = announcement(AddressFamily::Ipv4, "500");
+
+        assert_eq!(withdrawal.encode_withdrawal(), "FSR_SMC_BYE:500");
+        assert_eq!(
+            Announcement::parse("FSR_SMC_BYE:500"),
+            Some(Announcement {
+                is_withdrawal: true,
+                ..withdrawal
+            })
+        );
+    }
+
+    #[test]
+    fn versioned_withdrawal_round_trip() {
+        let withdrawal = Announcement {
+            address: Some("192.168.1.10".parse().unwrap()),
+            via: vec!["reflector-a".to_string()],
+            is_withdrawal: true,
+            ..announcement(AddressFamily::Ipv4, "500")
+        };
+        let packet = withdrawal.encode_versioned();
+
+        assert_eq!(
+            packet,
+            "FSR_SMC_BYE;af=4;port=500;addr=192.168.1.10;via=reflector-a"
+        );
+        assert_eq!(Announcement::parse(&packet), Some(withdrawal));
+    }
+
+    #[test]
+    fn malformed_packets_are_rejected() {
+        for packet in [
+            "",
+            "FSR_SMC",
+            "FSR_SMC:",
+            "FSR_SMC:port",
+            "FSR_SMC:70000",
+            "FSR_SMC500",
+            "fsr_smc:500",
+            "FSR_SMC_BYE:",
+            "FSR_SMC_BYE",
+            "FSR_SMC_BYE;port=500",
+            "FSR_SMC2",
+            "FSR_SMC2;",
+            "FSR_SMC2;port=500",
+            "FSR_SMC2;af=4",
+            "FSR_SMC2;af=5;port=500",
+            "FSR_SMC2;af=4;port=",
+            "FSR_SMC2;af=4;port=abc",
+        ] {
+            assert_eq!(Announcement::parse(packet), None, "{}", packet);
+        }
+    }
+
+    #[test]
+    fn invalid_address_is_ignored() {
+        assert_eq!(
+            Announcement::parse("FSR_SMC2;af=4;port=500;addr=not-an-ip"),
+            Some(announcement(AddressFamily::Ipv4, "500"))
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use serde_derive::Serialize;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener};
+
+use crate::settings::PortSettings;
+
+use super::msfs::find_msfs_process_ids;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum PortDecision {
+    /// Nothing listens on the port yet.
+    Free,
+    /// The port is held by the running simulator, which is expected.
+    OwnedBySimulator,
+    /// The configured port was taken, so the next free one from the range was chosen.
+    Reassigned { from: u16, to: u16 },
+    /// The port is taken by another application and no replacement was picked.
+    Conflict { owner_pid: Option<u32> },
+}
+
+impl PortDecision {
+    pub fn describe(&self, port: &str) -> String {
+        match self {
+            PortDecision::Free => format!("Port {} is available.", port),
+            PortDecision::OwnedBySimulator => format!("Port {} is used by the simulator.", port),
+            PortDecision::Reassigned { from, to } => {
+                format!("Port {} was busy, switched to {}.", from, to)
+            }
+            PortDecision::Conflict {
+                owner_pid: Some(pid),
+            } => format!("Port {} is used by another process (PID {}).", port, pid),
+            PortDecision::Conflict { owner_pid: None } => {
+                format!("Port {} is used by another application.", port)
+            }
+        }
+    }
+}
+
+pub fn is_tcp_port_free(address: IpAddr, port: u16) -> bool {
+    TcpListener::bind((address, port)).is_ok()
+}
+
+/// Reads the IPv4 TCP table of the given class.
+#[cfg(windows)]
+fn read_tcp_table(
+    table_class: windows_sys::Win32::NetworkManagement::IpHelper::TCP_TABLE_CLASS,
+) -> Vec<windows_sys::Win32::NetworkManagement::IpHelper::MIB_TCPROW_OWNER_PID> {
+    use std::{mem::size_of, ptr::null_mut, slice};
+    use windows_sys::Win32::{
+        NetworkManagement::IpHelper::{
+            GetExtendedTcpTable, MIB_TCPROW_OWNER_PID, MIB_TCPTABLE_OWNER_PID,
+        },
+        Networking::WinSock::AF_INET,
+    };
+
+    unsafe {
+        let mut size: u32 = 0;
+        GetExtendedTcpTable(null_mut(), &mut size, 0, AF_INET as u32, table_class, 0);
+
+        /* u32 buffer keeps the table rows aligned */
+        let mut buffer = vec![0u32; size as usize / size_of::<u32>() + 1];
+
+        let result = GetExtendedTcpTable(
+            buffer.as_mut_ptr() as *mut _,
+            &mut size,
+            0,
+            AF_INET as u32,
+            table_class,
+            0,
+        );
+
+        if result != 0 {
+            return Vec::new();
+        }
+
+        let table = buffer.as_ptr() as *const MIB_TCPTABLE_OWNER_PID;
+        let rows: &[MIB_TCPROW_OWNER_PID] =
+            slice::from_raw_parts((*table).table.as_ptr(), (*table).dwNumEntries as usize);
+
+        rows.to_vec()
+    }
+}
+
+/// Finds the process listening on the TCP port.
+#[cfg(windows)]
+pub fn find_tcp_port_owner(port: u16) -> Option<u32> {
+    use windows_sys::Win32::NetworkManagement::IpHelper::TCP_TABLE_OWNER_PID_LISTENER;
+
+    read_tcp_table(TCP_TABLE_OWNER_PID_LISTENER)
+        .iter()
+        .find(|row| u16::from_be(row.dwLocalPort as u16) == port)
+        .map(|row| row.dwOwningPid)
+}
+
+#[cfg(not(windows))]
+pub fn find_tcp_port_owner(_port: u16) -> Option<u32> {
+    None
+}
+
+/// Remote ends of the established connections to the local TCP port.
+#[cfg(windows)]
+pub fn find_tcp_connections(port: u16) -> Vec<SocketAddr> {
+    use windows_sys::Win32::NetworkManagement::IpHelper::{
+        MIB_TCP_STATE_ESTAB, TCP_TABLE_OWNER_PID_CONNECTIONS,
+    };
+
+    read_tcp_table(TCP_TABLE_OWNER_PID_CONNECTIONS)
+        .iter()
+        .filter(|row| {
+            row.dwState == MIB_TCP_STATE_ESTAB as u32
+                && u16::from_be(row.dwLocalPort as u16) == port
+        })
+        .map(|row| {
+            SocketAddr::new(
+                IpAddr::V4(Ipv4Addr::from(u32::from_be(row.dwRemoteAddr))),
+                u16::from_be(row.dwRemotePort as u16),
+            )
+        })
+        .collect()
+}
+
+#[cfg(not(windows))]
+pub fn find_tcp_connections(_port: u16) -> Vec<SocketAddr> {
+    Vec::new()
+}
+
+/// Checks whether SimConnect can open `port` on `address`.
+/// With the simulator running an occupied port is confirmed to be its own.
+pub fn check_simconnect_port(
+    address: IpAddr,
+    port: u16,
+    is_msfs_running: bool,
+    settings: &PortSettings,
+) -> PortDecision {
+    if is_tcp_port_free(address, port) {
+        return PortDecision::Free;
+    }
+
+    let owner_pid = find_tcp_port_owner(port);
+
+    if is_msfs_running {
+        match owner_pid {
+            /* Owner lookup is unavailable, the simulator is the most likely holder */
+            None => return PortDecision::OwnedBySimulator,
+            Some(pid) if find_msfs_process_ids().contains(&pid) => {
+                return PortDecision::OwnedBySimulator
+            }
+            _ => {}
+        }
+    }
+
+    if settings.auto_select {
+        if let Some(free_port) = (settings.range_start..=settings.range_end)
+            .filter(|candidate| *candidate != port)
+            .find(|candidate| is_tcp_port_free(address, *candidate))
+        {
+            return PortDecision::Reassigned {
+                from: port,
+                to: free_port,
+            };
+        }
+    }
+
+    PortDecision::Conflict { owner_pid }
+}
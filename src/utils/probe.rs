@@ -0,0 +1,271 @@
+use std::{
+    io::{Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream},
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+use winit::event_loop::EventLoopProxy;
+
+use crate::state::AppEvent;
+
+use super::metrics::metrics;
+
+/* FSX SP2 protocol, still accepted by MSFS */
+static SIMCONNECT_PROTOCOL_VERSION: u32 = 4;
+static SIMCONNECT_OPEN_PACKET_ID: u32 = 0xF000_0001;
+static SIMCONNECT_RECV_ID_OPEN: u32 = 2;
+static SIMCONNECT_PROBE_APP_NAME: &str = "FSRewire-client probe";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProbeResult {
+    /// The endpoint accepted a TCP connection, `handshake` is set when the Open exchange was tried.
+    Listening {
+        latency: Duration,
+        handshake: Option<bool>,
+    },
+    NotAccepting,
+}
+
+/// Maps a wildcard listen address to loopback, anything else is probed as is.
+pub fn probe_target(address: &str, port: &str) -> Option<SocketAddr> {
+    let address = match address.parse::<IpAddr>().ok()? {
+        IpAddr::V4(address) if address.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(address) if address.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        address => address,
+    };
+
+    Some(SocketAddr::new(address, port.parse().ok()?))
+}
+
+fn build_open_packet() -> Vec<u8> {
+    let mut body = Vec::new();
+
+    let mut app_name = [0u8; 256];
+    app_name[..SIMCONNECT_PROBE_APP_NAME.len()]
+        .copy_from_slice(SIMCONNECT_PROBE_APP_NAME.as_bytes());
+    body.extend_from_slice(&app_name);
+    body.extend_from_slice(&0u32.to_le_bytes());
+    body.push(0);
+    body.extend_from_slice(b"XSF");
+    /* Client version 10.0.61259.0 (FSX SP2) */
+    for version_part in [10u32, 0, 61259, 0] {
+        body.extend_from_slice(&version_part.to_le_bytes());
+    }
+
+    let mut packet = Vec::with_capacity(16 + body.len());
+    packet.extend_from_slice(&(16 + body.len() as u32).to_le_bytes());
+    packet.extend_from_slice(&SIMCONNECT_PROTOCOL_VERSION.to_le_bytes());
+    packet.extend_from_slice(&SIMCONNECT_OPEN_PACKET_ID.to_le_bytes());
+    packet.extend_from_slice(&1u32.to_le_bytes());
+    packet.extend(body);
+
+    packet
+}
+
+/// Sends the SimConnect Open request and waits for the matching `RECV_OPEN` header.
+fn perform_open_handshake(stream: &mut TcpStream, timeout: Duration) -> bool {
+    if stream.set_read_timeout(Some(timeout)).is_err() {
+        return false;
+    }
+
+    if stream.write_all(&build_open_packet()).is_err() {
+        return false;
+    }
+
+    let mut header = [0u8; 12];
+
+    match stream.read_exact(&mut header) {
+        Ok(_) => {
+            let recv_id = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+            recv_id == SIMCONNECT_RECV_ID_OPEN
+        }
+        Err(_) => false,
+    }
+}
+
+pub fn probe_endpoint(target: SocketAddr, timeout: Duration, handshake: bool) -> ProbeResult {
+    let started_at = Instant::now();
+
+    match TcpStream::connect_timeout(&target, timeout) {
+        Ok(mut stream) => {
+            let latency = started_at.elapsed();
+
+            ProbeResult::Listening {
+                latency,
+                handshake: handshake.then(|| perform_open_handshake(&mut stream, timeout)),
+            }
+        }
+        Err(_) => ProbeResult::NotAccepting,
+    }
+}
+
+/// Probes `target` every `interval`, a new target can be sent through `retarget` (e.g. after the address changed).
+pub fn endpoint_probe_thread(
+    proxy: EventLoopProxy<AppEvent>,
+    retarget: mpsc::Receiver<SocketAddr>,
+    mut target: SocketAddr,
+    interval: Duration,
+    timeout: Duration,
+    handshake: bool,
+) {
+    loop {
+        let result = probe_endpoint(target, timeout, handshake);
+        log::debug!("Probe of {}: {:?}", target, result);
+
+        metrics().set_probe_latency(match result {
+            ProbeResult::Listening { latency, .. } => Some(latency),
+            ProbeResult::NotAccepting => None,
+        });
+
+        if proxy.send_event(AppEvent::EndpointProbed(result)).is_err() {
+            break;
+        }
+
+        match retarget.recv_timeout(interval) {
+            Ok(new_target) => target = new_target,
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::TcpListener, thread};
+
+    static TIMEOUT: Duration = Duration::from_millis(500);
+
+    /// Accepts one connection, reads the Open request and answers with a header carrying `recv_id`.
+    fn fake_simconnect(recv_id: u32) -> (SocketAddr, thread::JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+
+            let mut size = [0u8; 4];
+            stream.read_exact(&mut size).unwrap();
+            let mut request = size.to_vec();
+            request.resize(u32::from_le_bytes(size) as usize, 0);
+            stream.read_exact(&mut request[4..]).unwrap();
+
+            let mut reply = Vec::new();
+            for field in [12u32, SIMCONNECT_PROTOCOL_VERSION, recv_id] {
+                reply.extend_from_slice(&field.to_le_bytes());
+            }
+            stream.write_all(&reply).unwrap();
+
+            request
+        });
+
+        (address, server)
+    }
+
+    #[test]
+    fn listening_without_handshake() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+
+        let result = probe_endpoint(listener.local_addr().unwrap(), TIMEOUT, false);
+
+        assert!(matches!(
+            result,
+            ProbeResult::Listening {
+                handshake: None,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn closed_port_is_not_accepting() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+
+        assert_eq!(
+            probe_endpoint(address, TIMEOUT, false),
+            ProbeResult::NotAccepting
+        );
+    }
+
+    #[test]
+    fn unreachable_endpoint_gives_up_after_timeout() {
+        /* TEST-NET-1, never routed */
+        let target = SocketAddr::from(([192, 0, 2, 1], 500));
+        let started_at = Instant::now();
+
+        assert_eq!(
+            probe_endpoint(target, TIMEOUT, false),
+            ProbeResult::NotAccepting
+        );
+        assert!(started_at.elapsed() < TIMEOUT * 4);
+    }
+
+    #[test]
+    fn silent_endpoint_fails_handshake_after_timeout() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let started_at = Instant::now();
+
+        let result = probe_endpoint(listener.local_addr().unwrap(), TIMEOUT, true);
+
+        assert!(matches!(
+            result,
+            ProbeResult::Listening {
+                handshake: Some(false),
+                ..
+            }
+        ));
+        assert!(started_at.elapsed() >= TIMEOUT);
+    }
+
+    #[test]
+    fn handshake_accepts_open_reply() {
+        let (address, server) = fake_simconnect(SIMCONNECT_RECV_ID_OPEN);
+
+        let result = probe_endpoint(address, TIMEOUT, true);
+        let request = server.join().unwrap();
+
+        assert!(matches!(
+            result,
+            ProbeResult::Listening {
+                handshake: Some(true),
+                ..
+            }
+        ));
+        assert_eq!(request, build_open_packet());
+        assert_eq!(
+            u32::from_le_bytes(request[8..12].try_into().unwrap()),
+            SIMCONNECT_OPEN_PACKET_ID
+        );
+    }
+
+    #[test]
+    fn handshake_rejects_other_reply() {
+        let (address, server) = fake_simconnect(SIMCONNECT_RECV_ID_OPEN + 1);
+
+        let result = probe_endpoint(address, TIMEOUT, true);
+        server.join().unwrap();
+
+        assert!(matches!(
+            result,
+            ProbeResult::Listening {
+                handshake: Some(false),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn wildcard_target_is_probed_on_loopback() {
+        assert_eq!(
+            probe_target("0.0.0.0", "500"),
+            Some(SocketAddr::from(([127, 0, 0, 1], 500)))
+        );
+        assert_eq!(
+            probe_target("::", "500").map(|target| target.ip()),
+            Some(IpAddr::V6(Ipv6Addr::LOCALHOST))
+        );
+        assert_eq!(probe_target("192.168.1.10", "port"), None);
+    }
+}
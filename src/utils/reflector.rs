@@ -0,0 +1,235 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use crate::settings::ReflectorSettings;
+
+use super::{
+    net::{list_interfaces, ExposedInterface, NetInterface},
+    packet::{AddressFamily, Announcement},
+    udp::{bind_socket, UDP_BROADCAST_PORT},
+};
+
+/// Interfaces are listed again after this time, so new or renumbered adapters are picked up.
+static REFLECTOR_INTERFACE_REFRESH: Duration = Duration::from_secs(30);
+
+/// Random ID of this reflector, carried in the `via` field of forwarded announcements.
+pub fn generate_instance_id() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    format!("{:08x}", hasher.finish() as u32)
+}
+
+/// IPv4 interfaces taking part in the reflection, all non-loopback ones when none are configured.
+fn reflector_interfaces(settings: &ReflectorSettings) -> Result<Vec<NetInterface>, String> {
+    Ok(list_interfaces()?
+        .into_iter()
+        .filter(|interface| match interface.address {
+            IpAddr::V4(address) => {
+                !address.is_loopback()
+                    && (settings.interfaces.is_empty()
+                        || settings.interfaces.iter().any(|selected| {
+                            interface.name == *selected || address.to_string() == *selected
+                        }))
+            }
+            IpAddr::V6(_) => false,
+        })
+        .collect())
+}
+
+/// Builds the forwarded announcement or withdrawal, `None` for packets that must not be reflected.
+fn reflect(packet: &str, source: Ipv4Addr, instance_id: &str) -> Option<Announcement> {
+    let announcement = Announcement::parse(packet)?;
+
+    /* An own ID means the packet already went through this reflector */
+    if announcement.family != AddressFamily::Ipv4
+        || announcement.via.iter().any(|via| via == instance_id)
+    {
+        return None;
+    }
+
+    let mut via = announcement.via;
+    via.push(instance_id.to_string());
+
+    Some(Announcement {
+        family: AddressFamily::Ipv4,
+        port: announcement.port,
+        /* The datagram will come from this machine, so the host is kept in the payload */
+        address: announcement.address.or(Some(IpAddr::V4(source))),
+        via,
+        is_withdrawal: announcement.is_withdrawal,
+    })
+}
+
+/// Listens for announcements and forwards them to every other interface. Runs until an error.
+pub fn run_reflector(settings: &ReflectorSettings) -> Result<(), String> {
+    let instance_id = generate_instance_id();
+
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, UDP_BROADCAST_PORT))
+        .map_err(|e| format!("Error listening on port {}: {}", UDP_BROADCAST_PORT, e))?;
+
+    let mut interfaces = reflector_interfaces(settings)?;
+    let mut listed_at = Instant::now();
+
+    log::info!(
+        "Reflector {} listening on port {}, interfaces: {}",
+        instance_id,
+        UDP_BROADCAST_PORT,
+        interfaces
+            .iter()
+            .map(|interface| format!("{} ({})", interface.name, interface.address))
+            .collect::<Vec<String>>()
+            .join(", ")
+    );
+
+    let mut buffer = [0u8; 512];
+
+    loop {
+        let (length, source) = socket
+            .recv_from(&mut buffer)
+            .map_err(|e| format!("Error receiving announcement: {}", e))?;
+
+        let SocketAddr::V4(source) = source else {
+            continue;
+        };
+
+        let Ok(packet) = std::str::from_utf8(&buffer[..length]) else {
+            continue;
+        };
+
+        let Some(announcement) = reflect(packet, *source.ip(), &instance_id) else {
+            continue;
+        };
+
+        if listed_at.elapsed() > REFLECTOR_INTERFACE_REFRESH {
+            match reflector_interfaces(settings) {
+                Ok(listed) => interfaces = listed,
+                Err(error) => log::warn!("{}", error),
+            }
+            listed_at = Instant::now();
+        }
+
+        let reflected = announcement.encode_versioned();
+
+        for interface in interfaces
+            .iter()
+            .filter(|interface| !interface.contains(source.ip()))
+        {
+            let (IpAddr::V4(address), Some(broadcast)) = (interface.address, interface.broadcast)
+            else {
+                continue;
+            };
+
+            let target = SocketAddrV4::new(broadcast, UDP_BROADCAST_PORT);
+
+            match bind_socket(&ExposedInterface { address, broadcast }) {
+                Some(socket) => {
+                    if let Err(e) = socket.send_to(reflected.as_bytes(), target) {
+                        log::warn!("Reflecting to {} failed: {}", target, e);
+                    } else {
+                        log::debug!("Reflected '{}' from {} to {}", reflected, source, target);
+                    }
+                }
+                None => log::warn!("Unable to open broadcast socket on {}", address),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SOURCE: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 10);
+
+    #[test]
+    fn announcement_is_reflected_with_source_and_id() {
+        let reflected = reflect("FSR_SMC:500", SOURCE, "reflector-a").unwrap();
+
+        assert_eq!(
+            reflected.encode_versioned(),
+            "FSR_SMC2;af=4;port=500;addr=192.168.1.10;via=reflector-a"
+        );
+    }
+
+    #[test]
+    fn reflected_announcement_keeps_the_original_host() {
+        let reflected = reflect(
+            "FSR_SMC2;af=4;port=500;addr=10.0.0.5;via=reflector-a",
+            SOURCE,
+            "reflector-b",
+        )
+        .unwrap();
+
+        assert_eq!(
+            reflected.encode_versioned(),
+            "FSR_SMC2;af=4;port=500;addr=10.0.0.5;via=reflector-a,reflector-b"
+        );
+    }
+
+    #[test]
+    fn own_and_ipv6_packets_are_not_reflected() {
+        assert_eq!(
+            reflect(
+                "FSR_SMC2;af=4;port=500;via=reflector-a",
+                SOURCE,
+                "reflector-a"
+            ),
+            None
+        );
+        assert_eq!(
+            reflect(
+                "FSR_SMC2;af=4;port=500;via=reflector-b,reflector-a",
+                SOURCE,
+                "reflector-a"
+            ),
+            None
+        );
+        assert_eq!(
+            reflect("FSR_SMC2;af=6;port=500", SOURCE, "reflector-a"),
+            None
+        );
+        assert_eq!(reflect("hello", SOURCE, "reflector-a"), None);
+    }
+
+    #[test]
+    fn withdrawal_is_reflected() {
+        let reflected = reflect("FSR_SMC_BYE:500", SOURCE, "reflector-a").unwrap();
+
+        assert!(reflected.is_withdrawal);
+        assert_eq!(
+            reflected.encode_versioned(),
+            "FSR_SMC_BYE;af=4;port=500;addr=192.168.1.10;via=reflector-a"
+        );
+    }
+
+    #[test]
+    fn reflected_withdrawal_keeps_the_original_host() {
+        let reflected = reflect(
+            "FSR_SMC_BYE;af=4;port=500;addr=10.0.0.5;via=reflector-a",
+            SOURCE,
+            "reflector-b",
+        )
+        .unwrap();
+
+        assert_eq!(
+            reflected.encode_versioned(),
+            "FSR_SMC_BYE;af=4;port=500;addr=10.0.0.5;via=reflector-a,reflector-b"
+        );
+    }
+
+    #[test]
+    fn own_withdrawal_is_not_reflected() {
+        assert_eq!(
+            reflect(
+                "FSR_SMC_BYE;af=4;port=500;via=reflector-a",
+                SOURCE,
+                "reflector-a"
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,413 @@
+use serde_derive::Serialize;
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+    net::{IpAddr, Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::net::Ipv4Cidr;
+
+static RELAY_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+static RELAY_BUFFER_SIZE: usize = 16 * 1024;
+
+/// Peers allowed to use the relay, an empty list allows everyone.
+#[derive(Debug, Clone, Default)]
+pub struct AllowList {
+    networks: Vec<Ipv4Cidr>,
+    addresses: Vec<IpAddr>,
+}
+
+impl AllowList {
+    /// Accepts IPv4 CIDRs (`192.168.1.0/24`) and single IPv4 or IPv6 addresses.
+    pub fn parse(entries: &[String]) -> Result<Self, String> {
+        let mut allow_list = AllowList::default();
+
+        for entry in entries {
+            match entry.trim().parse::<IpAddr>() {
+                Ok(address) => allow_list.addresses.push(address),
+                Err(_) => allow_list.networks.push(entry.parse()?),
+            }
+        }
+
+        Ok(allow_list)
+    }
+
+    pub fn allows(&self, peer: &IpAddr) -> bool {
+        if self.networks.is_empty() && self.addresses.is_empty() {
+            return true;
+        }
+
+        let peer = match peer {
+            IpAddr::V6(address) => address
+                .to_ipv4_mapped()
+                .map(IpAddr::V4)
+                .unwrap_or(IpAddr::V6(*address)),
+            address => *address,
+        };
+
+        self.addresses.contains(&peer)
+            || match peer {
+                IpAddr::V4(address) => self
+                    .networks
+                    .iter()
+                    .any(|network| network.contains(&address)),
+                IpAddr::V6(_) => false,
+            }
+    }
+}
+
+#[derive(Debug)]
+struct RelayConnection {
+    peer: SocketAddr,
+    /// Unix timestamp (seconds) of the accepted connection.
+    connected_at: u64,
+    bytes_to_sim: AtomicU64,
+    bytes_to_peer: AtomicU64,
+}
+
+/// Copy of a relayed connection for the status API and window.
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayConnectionInfo {
+    pub peer: SocketAddr,
+    pub connected_at: u64,
+    pub bytes_to_sim: u64,
+    pub bytes_to_peer: u64,
+}
+
+/// Open relay connections with their byte counts.
+#[derive(Default)]
+pub struct RelayRegistry {
+    next_id: AtomicU64,
+    connections: Mutex<BTreeMap<u64, Arc<RelayConnection>>>,
+}
+
+impl RelayRegistry {
+    fn register(&self, peer: SocketAddr) -> (u64, Arc<RelayConnection>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let connection = Arc::new(RelayConnection {
+            peer,
+            connected_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|since_epoch| since_epoch.as_secs())
+                .unwrap_or(0),
+            bytes_to_sim: AtomicU64::new(0),
+            bytes_to_peer: AtomicU64::new(0),
+        });
+
+        if let Ok(mut connections) = self.connections.lock() {
+            connections.insert(id, connection.clone());
+        }
+
+        (id, connection)
+    }
+
+    fn unregister(&self, id: u64) {
+        if let Ok(mut connections) = self.connections.lock() {
+            connections.remove(&id);
+        }
+    }
+
+    pub fn connections(&self) -> Vec<RelayConnectionInfo> {
+        let Ok(connections) = self.connections.lock() else {
+            return Vec::new();
+        };
+
+        connections
+            .values()
+            .map(|connection| RelayConnectionInfo {
+                peer: connection.peer,
+                connected_at: connection.connected_at,
+                bytes_to_sim: connection.bytes_to_sim.load(Ordering::Relaxed),
+                bytes_to_peer: connection.bytes_to_peer.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+/// Copies until EOF or an error, then closes the write half so the other side sees the EOF too.
+fn pipe(mut from: TcpStream, mut to: TcpStream, counter: &AtomicU64) {
+    let mut buffer = vec![0u8; RELAY_BUFFER_SIZE];
+
+    loop {
+        let length = match from.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(length) => length,
+        };
+
+        if to.write_all(&buffer[..length]).is_err() {
+            break;
+        }
+
+        counter.fetch_add(length as u64, Ordering::Relaxed);
+    }
+
+    to.shutdown(Shutdown::Write).ok();
+}
+
+fn relay_connection(peer_stream: TcpStream, target: SocketAddr, connection: &RelayConnection) {
+    let sim_stream = match TcpStream::connect_timeout(&target, RELAY_CONNECT_TIMEOUT) {
+        Ok(stream) => stream,
+        Err(e) => {
+            log::warn!("Relay of {} to {} failed: {}", connection.peer, target, e);
+            return;
+        }
+    };
+
+    let (Ok(peer_reader), Ok(sim_reader)) = (peer_stream.try_clone(), sim_stream.try_clone())
+    else {
+        return;
+    };
+
+    thread::scope(|scope| {
+        scope.spawn(|| pipe(peer_reader, sim_stream, &connection.bytes_to_sim));
+        pipe(sim_reader, peer_stream, &connection.bytes_to_peer);
+    });
+}
+
+/// Accepts connections on `listener` and forwards allowed ones to the current `target`.
+/// The target is shared so it can follow SimConnect.xml rewrites.
+pub fn relay_thread(
+    listener: TcpListener,
+    target: Arc<Mutex<SocketAddr>>,
+    allow_list: AllowList,
+    registry: Arc<RelayRegistry>,
+) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::debug!("Relay accept failed: {}", e);
+                continue;
+            }
+        };
+
+        let Ok(peer) = stream.peer_addr() else {
+            continue;
+        };
+
+        if !allow_list.allows(&peer.ip()) {
+            log::warn!("Relay connection from {} rejected by the allow-list", peer);
+            stream.shutdown(Shutdown::Both).ok();
+            continue;
+        }
+
+        let Ok(target) = target.lock().map(|target| *target) else {
+            break;
+        };
+        let registry = registry.clone();
+
+        thread::spawn(move || {
+            let (id, connection) = registry.register(peer);
+            log::info!("Relaying {} to {}", peer, target);
+
+            relay_connection(stream, target, &connection);
+
+            log::info!(
+                "Relay of {} closed, {} bytes to the simulator, {} bytes back",
+                peer,
+                connection.bytes_to_sim.load(Ordering::Relaxed),
+                connection.bytes_to_peer.load(Ordering::Relaxed)
+            );
+            registry.unregister(id);
+        });
+    }
+}
+
+/// Binds the relay listener and serves it on its own thread.
+pub fn start_relay(
+    bind: SocketAddr,
+    target: Arc<Mutex<SocketAddr>>,
+    allow_list: AllowList,
+    registry: Arc<RelayRegistry>,
+) -> Result<(), String> {
+    let listener =
+        TcpListener::bind(bind).map_err(|e| format!("Error starting relay on {}: {}", bind, e))?;
+
+    log::info!("Relay listening on {}", bind);
+
+    thread::spawn(move || relay_thread(listener, target, allow_list, registry));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::Ipv4Addr, time::Instant};
+
+    static WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn echo_server() -> SocketAddr {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                thread::spawn(move || {
+                    let reader = stream.try_clone().unwrap();
+                    pipe(reader, stream, &AtomicU64::new(0));
+                });
+            }
+        });
+
+        address
+    }
+
+    fn relay(target: SocketAddr, allow: &[&str]) -> (SocketAddr, Arc<RelayRegistry>) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let allow_list = AllowList::parse(
+            &allow
+                .iter()
+                .map(|entry| entry.to_string())
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+        let registry = Arc::new(RelayRegistry::default());
+        let relay_registry = registry.clone();
+
+        thread::spawn(move || {
+            relay_thread(
+                listener,
+                Arc::new(Mutex::new(target)),
+                allow_list,
+                relay_registry,
+            )
+        });
+
+        (address, registry)
+    }
+
+    /// Counters are updated right after the write, so they can trail what the client has read.
+    fn wait_for_connections(
+        registry: &RelayRegistry,
+        condition: impl Fn(&[RelayConnectionInfo]) -> bool,
+    ) -> Vec<RelayConnectionInfo> {
+        let started_at = Instant::now();
+
+        loop {
+            let connections = registry.connections();
+
+            if condition(&connections) || started_at.elapsed() > WAIT_TIMEOUT {
+                return connections;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn allow_list(entries: &[&str]) -> Result<AllowList, String> {
+        AllowList::parse(
+            &entries
+                .iter()
+                .map(|entry| entry.to_string())
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn empty_allow_list_allows_everyone() {
+        let allow_list = allow_list(&[]).unwrap();
+
+        assert!(allow_list.allows(&ip("203.0.113.9")));
+        assert!(allow_list.allows(&ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn allow_list_matches_networks_and_addresses() {
+        let allow_list = allow_list(&["192.168.1.0/24", "10.0.0.5", "2001:db8::1"]).unwrap();
+
+        assert!(allow_list.allows(&ip("192.168.1.20")));
+        assert!(!allow_list.allows(&ip("192.168.2.20")));
+        assert!(allow_list.allows(&ip("10.0.0.5")));
+        assert!(!allow_list.allows(&ip("10.0.0.6")));
+        assert!(allow_list.allows(&ip("2001:db8::1")));
+        assert!(!allow_list.allows(&ip("2001:db8::2")));
+    }
+
+    #[test]
+    fn allow_list_unmaps_ipv4_mapped_peers() {
+        let allow_list = allow_list(&["192.168.1.0/24"]).unwrap();
+
+        assert!(allow_list.allows(&ip("::ffff:192.168.1.20")));
+        assert!(!allow_list.allows(&ip("::ffff:192.168.2.20")));
+    }
+
+    #[test]
+    fn allow_list_rejects_invalid_entries() {
+        assert!(allow_list(&["192.168.1.0/40"]).is_err());
+        assert!(allow_list(&["lan"]).is_err());
+    }
+
+    #[test]
+    fn bytes_round_trip_and_are_counted() {
+        let (address, registry) = relay(echo_server(), &["127.0.0.0/8"]);
+        let message = b"SimConnect Open request".repeat(100);
+
+        let mut client = TcpStream::connect(address).unwrap();
+        client.set_read_timeout(Some(WAIT_TIMEOUT)).unwrap();
+        client.write_all(&message).unwrap();
+
+        let mut echoed = vec![0u8; message.len()];
+        client.read_exact(&mut echoed).unwrap();
+        assert_eq!(echoed, message);
+
+        let connections = wait_for_connections(&registry, |connections| {
+            /* Counted after each write, so the echo can arrive before the count */
+            connections.iter().any(|connection| {
+                connection.bytes_to_sim == message.len() as u64
+                    && connection.bytes_to_peer == message.len() as u64
+            })
+        });
+
+        assert_eq!(connections.len(), 1);
+        assert_eq!(connections[0].peer, client.local_addr().unwrap());
+        assert_eq!(connections[0].bytes_to_sim, message.len() as u64);
+        assert_eq!(connections[0].bytes_to_peer, message.len() as u64);
+
+        drop(client);
+
+        assert!(wait_for_connections(&registry, |connections| connections.is_empty()).is_empty());
+    }
+
+    #[test]
+    fn peers_outside_the_allow_list_are_rejected() {
+        let (address, registry) = relay(echo_server(), &["192.168.1.0/24", "10.0.0.5"]);
+
+        let mut client = TcpStream::connect(address).unwrap();
+        client.set_read_timeout(Some(WAIT_TIMEOUT)).unwrap();
+        client.write_all(b"hello").ok();
+
+        let mut buffer = [0u8; 16];
+        let is_closed = matches!(client.read(&mut buffer), Ok(0) | Err(_));
+
+        assert!(is_closed);
+        assert!(registry.connections().is_empty());
+    }
+
+    #[test]
+    fn unreachable_target_closes_the_peer() {
+        let closed = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let target = closed.local_addr().unwrap();
+        drop(closed);
+
+        let (address, registry) = relay(target, &[]);
+
+        let mut client = TcpStream::connect(address).unwrap();
+        client.set_read_timeout(Some(WAIT_TIMEOUT)).unwrap();
+
+        let mut buffer = [0u8; 16];
+        assert!(matches!(client.read(&mut buffer), Ok(0) | Err(_)));
+        assert!(wait_for_connections(&registry, |connections| connections.is_empty()).is_empty());
+    }
+}
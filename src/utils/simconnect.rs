@@ -7,108 +7,295 @@ use quick_xml::se::Serializer as XmlSerializer;
 
 use super::file::{read_windows1252_file, write_windows1252_file};
 
-use crate::schema::simconnect::{SimBaseDocument, SimConnectComm};
+use crate::{
+    schema::simconnect::{SimBaseDocument, SimConnectComm},
+    settings::Settings,
+};
+
+use super::{
+    metrics::metrics,
+    port::{check_simconnect_port, PortDecision},
+};
 
-static SIMCONNECT_SERVER_ADDR: &str = "0.0.0.0";
 static SIMCONNECT_SERVER_PORT: &str = "500";
 
+#[derive(Debug, Clone)]
+pub struct SimConnectEndpoint {
+    pub address: String,
+    pub port: String,
+}
+
 pub struct SimConnectConfigResult {
     pub address: String,
     pub port: String,
+    pub ipv6: Option<SimConnectEndpoint>,
+    pub port_decision: PortDecision,
     pub is_changed: bool,
 }
 
-fn get_simconnect_xml_path() -> String {
-    if let Some(user_home) = dirs::home_dir() {
-        let sim_connect_file_name = "SimConnect.xml";
+pub struct SimInstallation {
+    pub edition: &'static str,
+    pub xml_path: String,
+    pub is_present: bool,
+}
 
-        let user_home_str = user_home
-            .to_str()
-            .expect("Failed to convert path to string");
+/// Known SimConnect.xml locations in the order they are preferred.
+pub fn find_sim_installations() -> Vec<SimInstallation> {
+    let Some(user_home) = dirs::home_dir() else {
+        return Vec::new();
+    };
 
-        let ms_store_filepath = format!(
-            "{}\\AppData\\Local\\Packages\\Microsoft.FlightSimulator_8wekyb3d8bbwe\\LocalCache\\{}",
-            user_home_str, sim_connect_file_name
-        );
-        let steam_edition_filepath = format!(
-            "{}\\AppData\\Roaming\\Microsoft Flight Simulator\\{}",
-            user_home_str, sim_connect_file_name
-        );
+    let sim_connect_file_name = "SimConnect.xml";
 
-        let ms_store_path_exists = std::path::Path::new(&ms_store_filepath).exists();
-        let steam_version_path_exists = std::path::Path::new(&steam_edition_filepath).exists();
+    let user_home_str = user_home
+        .to_str()
+        .expect("Failed to convert path to string");
 
-        if ms_store_path_exists {
-            ms_store_filepath
-        } else if steam_version_path_exists {
-            steam_edition_filepath
-        } else {
-            panic!("Unable to determine SimConnect XML path.");
-        }
-    } else {
-        panic!("Unable to determine user home directory.");
-    }
+    let ms_store_filepath = format!(
+        "{}\\AppData\\Local\\Packages\\Microsoft.FlightSimulator_8wekyb3d8bbwe\\LocalCache\\{}",
+        user_home_str, sim_connect_file_name
+    );
+    let steam_edition_filepath = format!(
+        "{}\\AppData\\Roaming\\Microsoft Flight Simulator\\{}",
+        user_home_str, sim_connect_file_name
+    );
+
+    [
+        ("MS Store", ms_store_filepath),
+        ("Steam", steam_edition_filepath),
+    ]
+    .into_iter()
+    .map(|(edition, xml_path)| SimInstallation {
+        edition,
+        is_present: std::path::Path::new(&xml_path).exists(),
+        xml_path,
+    })
+    .collect()
 }
 
-pub fn update_simconnect_config() -> Result<SimConnectConfigResult, String> {
-    let xml_file_path = get_simconnect_xml_path();
+/// Path of the preferred existing SimConnect.xml.
+pub fn find_simconnect_xml_path() -> Option<String> {
+    find_sim_installations()
+        .into_iter()
+        .find(|installation| installation.is_present)
+        .map(|installation| installation.xml_path)
+}
 
-    let xml_content = read_windows1252_file(&xml_file_path)?;
+/// Path of the SimConnect.xml to configure, an error when no simulator installation has one.
+pub fn get_simconnect_xml_path() -> Result<String, String> {
+    find_simconnect_xml_path()
+        .ok_or("SimConnect.xml not found, no simulator installation was detected.".to_string())
+}
 
-    let mut config: SimBaseDocument = xml_from_string(&xml_content)
-        .map_err(|e| format!("Error parsing SimConnect.xml: {}", e))?;
+/// Copy of the SimConnect.xml content from before the client first changed it.
+pub fn get_simconnect_backup_path(xml_file_path: &str) -> String {
+    format!("{}.fsrewire.bak", xml_file_path)
+}
+
+fn backup_simconnect_xml(xml_file_path: &str) -> Result<(), String> {
+    let backup_path = get_simconnect_backup_path(xml_file_path);
+
+    if std::path::Path::new(&backup_path).exists() {
+        return Ok(());
+    }
+
+    std::fs::copy(xml_file_path, &backup_path)
+        .map_err(|e| format!("Error backing up SimConnect.xml: {}", e))?;
+    log::info!("Original SimConnect.xml saved to {}", backup_path);
 
-    let mut ipv4_address = Some(SIMCONNECT_SERVER_ADDR.to_string());
-    let mut ipv4_port = Some(SIMCONNECT_SERVER_PORT.to_string());
-    let mut ipv4_found = false;
+    Ok(())
+}
+
+/// Finds the static section of the given protocol and points it to `server_address`,
+/// creating the section when it does not exist. The existing port is kept unless `forced_port` is set.
+fn apply_comm_section(
+    config: &mut SimBaseDocument,
+    protocol: &str,
+    description: &str,
+    server_address: &str,
+    default_port: &str,
+    forced_port: Option<&str>,
+) -> (SimConnectEndpoint, bool) {
     let mut is_config_changed = false;
 
     for comm_section in &mut config.simconnect_comm {
-        if comm_section.protocol == "IPv4" && !comm_section.description.contains("Dynamic") {
-            ipv4_address = match &comm_section.address {
-                Some(addr) => {
-                    if (addr != SIMCONNECT_SERVER_ADDR) {
-                        is_config_changed = true;
-                    }
-
-                    Some(SIMCONNECT_SERVER_ADDR.to_string())
-                }
-                None => {
+        if comm_section.protocol == protocol && !comm_section.description.contains("Dynamic") {
+            if comm_section.address.as_deref() != Some(server_address) {
+                is_config_changed = true;
+            }
+
+            let port = match (&comm_section.port, forced_port) {
+                (Some(port), None) => port.clone(),
+                (Some(port), Some(forced_port)) if port == forced_port => port.clone(),
+                (_, forced_port) => {
                     is_config_changed = true;
-                    Some(SIMCONNECT_SERVER_ADDR.to_string())
+                    forced_port.unwrap_or(default_port).to_string()
                 }
             };
 
-            ipv4_port = match &comm_section.port {
-                Some(port) => Some(port.clone()),
-                None => {
-                    is_config_changed = true;
-                    Some(SIMCONNECT_SERVER_PORT.to_string())
-                }
+            comm_section.address = Some(server_address.to_string());
+            comm_section.port = Some(port.clone());
+
+            let endpoint = SimConnectEndpoint {
+                address: server_address.to_string(),
+                port,
             };
 
-            comm_section.address = ipv4_address.clone();
-            comm_section.port = ipv4_port.clone();
+            return (endpoint, is_config_changed);
+        }
+    }
 
-            ipv4_found = true;
-            break;
+    let port = forced_port.unwrap_or(default_port).to_string();
+
+    config.simconnect_comm.push(SimConnectComm {
+        protocol: protocol.to_string(),
+        address: Some(server_address.to_string()),
+        port: Some(port.clone()),
+        description: description.to_string(),
+        scope: "local".to_string(),
+        max_clients: "64".to_string(),
+        max_recv_size: "4188".to_string(),
+    });
+
+    let endpoint = SimConnectEndpoint {
+        address: server_address.to_string(),
+        port,
+    };
+
+    (endpoint, true)
+}
+
+/// Runs the port preflight for an applied section and moves it to the chosen port when reassigned.
+fn preflight_comm_section(
+    config: &mut SimBaseDocument,
+    protocol: &str,
+    description: &str,
+    endpoint: SimConnectEndpoint,
+    settings: &Settings,
+    is_msfs_running: bool,
+) -> Result<(SimConnectEndpoint, PortDecision, bool), String> {
+    let address = endpoint
+        .address
+        .parse()
+        .map_err(|e| format!("Invalid SimConnect address '{}': {}", endpoint.address, e))?;
+    let port = endpoint
+        .port
+        .parse()
+        .map_err(|e| format!("Invalid SimConnect port '{}': {}", endpoint.port, e))?;
+
+    let port_decision = check_simconnect_port(address, port, is_msfs_running, &settings.port);
+
+    match &port_decision {
+        PortDecision::Free | PortDecision::OwnedBySimulator => {
+            log::info!("{} {}", protocol, port_decision.describe(&endpoint.port))
         }
+        _ => log::warn!("{} {}", protocol, port_decision.describe(&endpoint.port)),
     }
 
-    if !ipv4_found {
-        config.simconnect_comm.push(SimConnectComm {
-            protocol: "IPv4".to_string(),
-            address: ipv4_address.clone(),
-            port: ipv4_port.clone(),
-            description: "Static IP4 port".to_string(),
-            scope: "local".to_string(),
-            max_clients: "64".to_string(),
-            max_recv_size: "4188".to_string(),
-        });
-
-        is_config_changed = true;
+    if let PortDecision::Reassigned { to, .. } = &port_decision {
+        let (endpoint, _) = apply_comm_section(
+            config,
+            protocol,
+            description,
+            &endpoint.address,
+            SIMCONNECT_SERVER_PORT,
+            Some(&to.to_string()),
+        );
+
+        return Ok((endpoint, port_decision, true));
+    }
+
+    Ok((endpoint, port_decision, false))
+}
+
+pub fn has_simconnect_backup() -> bool {
+    find_simconnect_xml_path()
+        .map(|xml_path| std::path::Path::new(&get_simconnect_backup_path(&xml_path)).exists())
+        .unwrap_or(false)
+}
+
+/// Puts the SimConnect.xml from before the client's first change back in place.
+/// The backup is kept, so it still holds the original after the config is applied again.
+pub fn restore_simconnect_xml() -> Result<(), String> {
+    let xml_file_path =
+        find_simconnect_xml_path().ok_or("Unable to determine SimConnect XML path.")?;
+    let backup_path = get_simconnect_backup_path(&xml_file_path);
+
+    if !std::path::Path::new(&backup_path).exists() {
+        return Err("No backup of the original SimConnect.xml exists.".to_string());
     }
 
+    std::fs::copy(&backup_path, &xml_file_path)
+        .map_err(|e| format!("Error restoring SimConnect.xml: {}", e))?;
+    log::info!("Original SimConnect.xml restored from {}", backup_path);
+
+    Ok(())
+}
+
+pub fn read_simconnect_config() -> Result<SimBaseDocument, String> {
+    let xml_path = get_simconnect_xml_path()?;
+    let xml_content = read_windows1252_file(&xml_path)?;
+
+    xml_from_string(&xml_content).map_err(|e| format!("Error parsing SimConnect.xml: {}", e))
+}
+
+pub fn update_simconnect_config(
+    server_address: &str,
+    settings: &Settings,
+    is_msfs_running: bool,
+) -> Result<SimConnectConfigResult, String> {
+    let ipv6_settings = &settings.ipv6;
+
+    let xml_file_path = get_simconnect_xml_path()?;
+    log::info!("Using SimConnect.xml: {}", xml_file_path);
+
+    let xml_content = read_windows1252_file(&xml_file_path)?;
+
+    let mut config: SimBaseDocument = xml_from_string(&xml_content)
+        .map_err(|e| format!("Error parsing SimConnect.xml: {}", e))?;
+
+    let (ipv4_endpoint, mut is_config_changed) = apply_comm_section(
+        &mut config,
+        "IPv4",
+        "Static IP4 port",
+        server_address,
+        SIMCONNECT_SERVER_PORT,
+        None,
+    );
+
+    let (ipv4_endpoint, port_decision, is_port_changed) = preflight_comm_section(
+        &mut config,
+        "IPv4",
+        "Static IP4 port",
+        ipv4_endpoint,
+        settings,
+        is_msfs_running,
+    )?;
+    is_config_changed |= is_port_changed;
+
+    let ipv6_endpoint = if ipv6_settings.enabled {
+        let (endpoint, is_changed) = apply_comm_section(
+            &mut config,
+            "IPv6",
+            "Static IP6 port",
+            &ipv6_settings.address,
+            SIMCONNECT_SERVER_PORT,
+            Some(&ipv6_settings.port),
+        );
+        let (endpoint, _, is_port_changed) = preflight_comm_section(
+            &mut config,
+            "IPv6",
+            "Static IP6 port",
+            endpoint,
+            settings,
+            is_msfs_running,
+        )?;
+        is_config_changed |= is_changed || is_port_changed;
+        Some(endpoint)
+    } else {
+        None
+    };
+
     let mut output = String::new();
 
     output.push_str(r#"<?xml version="1.0" encoding="Windows-1252"?>"#);
@@ -119,11 +306,32 @@ pub fn update_simconnect_config() -> Result<SimConnectConfigResult, String> {
 
     config.serialize(ser).unwrap();
 
+    if is_config_changed {
+        backup_simconnect_xml(&xml_file_path)?;
+    }
+
     write_windows1252_file(&xml_file_path, &output)?;
 
+    if is_config_changed {
+        metrics().record_config_rewrite();
+    }
+
+    log::info!(
+        "SimConnect.xml {}, IPv4 endpoint {}:{}",
+        if is_config_changed {
+            "updated"
+        } else {
+            "unchanged"
+        },
+        ipv4_endpoint.address,
+        ipv4_endpoint.port
+    );
+
     Ok(SimConnectConfigResult {
-        address: ipv4_address.unwrap(),
-        port: ipv4_port.unwrap(),
+        address: ipv4_endpoint.address,
+        port: ipv4_endpoint.port,
+        ipv6: ipv6_endpoint,
+        port_decision,
         is_changed: is_config_changed,
     })
 }
@@ -1,43 +1,706 @@
-use std::{net::UdpSocket, sync::mpsc, thread, time::Duration};
+use serde_derive::Serialize;
+use std::{
+    net::{IpAddr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, ToSocketAddrs, UdpSocket},
+    sync::{mpsc, Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use winit::event_loop::EventLoopProxy;
 
-pub static UDP_THREAD_STATUS_OK: &str = "udp-ok";
-pub static UDP_THREAD_STATUS_ERROR: &str = "udp-error";
+use crate::{settings::UnicastSettings, state::AppEvent};
 
-static UDP_PACKET_PREFIX: &str = "FSR_SMC";
-static UDP_BROADCAST_ADDRESS: &str = "255.255.255.255:1234";
+use super::{
+    metrics::metrics,
+    net::{list_interfaces, ExposedInterface},
+    packet::{AddressFamily, Announcement},
+    simconnect::SimConnectEndpoint,
+};
 
-pub fn udp_broadcast_thread(sender: mpsc::Sender<String>, simconnect_port: String) {
-    let mut is_success_sent = false;
+pub static UDP_BROADCAST_PORT: u16 = 1234;
+static UDP_BROADCAST_INTERVAL: Duration = Duration::from_secs(10);
+/// Link-local all-nodes group, reachable on IPv6-only segments without any routing.
+static UDP_MULTICAST_V6_ADDRESS: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
 
-    let socket = match UdpSocket::bind("0.0.0.0:0") {
-        Ok(socket) => socket,
-        Err(_) => {
-            sender.send(UDP_THREAD_STATUS_ERROR.to_string()).unwrap();
-            return;
+pub enum BroadcastCommand {
+    /// Send the withdrawal and stay quiet until `Announce`, the sender is notified once it was sent.
+    Withdraw(Option<mpsc::Sender<()>>),
+    /// Announce immediately and resume the regular cycle.
+    Announce,
+}
+
+fn unix_now() -> Option<u64> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|since_epoch| since_epoch.as_secs())
+}
+
+/// Send status of one unicast target.
+#[derive(Debug, Clone, Serialize)]
+pub struct UnicastTargetStats {
+    pub target: String,
+    /// Addresses from the last successful resolution.
+    pub addresses: Vec<SocketAddr>,
+    pub packets_sent: u64,
+    pub send_errors: u64,
+    pub last_sent_at: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+/// Send status of the IPv4 broadcast or of one IPv6 multicast scope.
+#[derive(Debug, Clone, Serialize)]
+pub struct InterfaceStats {
+    /// e.g. `Ethernet (192.168.1.10 → 192.168.1.255)`.
+    pub interface: String,
+    pub packets_sent: u64,
+    pub send_errors: u64,
+    pub last_sent_at: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+impl InterfaceStats {
+    fn new(interface: String) -> Self {
+        InterfaceStats {
+            interface,
+            packets_sent: 0,
+            send_errors: 0,
+            last_sent_at: None,
+            last_error: None,
+        }
+    }
+
+    fn record(&mut self, result: Result<(), String>) {
+        match result {
+            Ok(_) => {
+                self.packets_sent += 1;
+                self.last_sent_at = unix_now();
+                self.last_error = None;
+            }
+            Err(error) => {
+                /* Logged once per failure streak, the broadcast cycle would repeat it every time */
+                if self.last_error.is_none() {
+                    log::warn!("Announcement on {}: {}", self.interface, error);
+                }
+
+                self.send_errors += 1;
+                self.last_error = Some(error);
+            }
         }
+    }
+}
+
+/// Counters shared between broadcaster restarts, read by the status API.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BroadcastStats {
+    pub packets_sent: u64,
+    pub send_errors: u64,
+    /// Unix timestamp (seconds) of the last successful announcement.
+    pub last_sent_at: Option<u64>,
+    /// Where the announcement goes, the IPv4 broadcast first and then the IPv6 scopes.
+    pub interfaces: Vec<InterfaceStats>,
+    pub unicast_targets: Vec<UnicastTargetStats>,
+}
+
+impl BroadcastStats {
+    fn record_sent(&mut self) {
+        self.packets_sent += 1;
+        self.last_sent_at = unix_now();
+    }
+}
+
+fn record_interface_result(
+    stats: &Mutex<BroadcastStats>,
+    index: usize,
+    result: Result<(), String>,
+) {
+    if let Some(interface_stats) = stats
+        .lock()
+        .ok()
+        .as_mut()
+        .and_then(|stats| stats.interfaces.get_mut(index))
+    {
+        interface_stats.record(result);
+    }
+}
+
+/// Splits `host` or `host:port`, the announcement port is the default.
+fn parse_unicast_target(target: &str) -> Result<(&str, u16), String> {
+    let (host, port) = match target.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse()
+                .map_err(|_| format!("Invalid port in unicast target {}", target))?,
+        ),
+        None => (target, UDP_BROADCAST_PORT),
     };
 
-    if let Err(_) = socket.set_broadcast(true) {
-        sender.send(UDP_THREAD_STATUS_ERROR.to_string()).unwrap();
-        return;
+    if host.is_empty() {
+        return Err(format!("Unicast target {} has no host", target));
+    }
+
+    Ok((host, port))
+}
+
+struct UnicastTarget {
+    target: String,
+    /// Addresses from the last successful resolution, kept while the name doesn't resolve.
+    addresses: Vec<SocketAddr>,
+    resolve_error: Option<String>,
+}
+
+impl UnicastTarget {
+    fn new(target: &str) -> Self {
+        UnicastTarget {
+            target: target.trim().to_string(),
+            addresses: Vec::new(),
+            resolve_error: None,
+        }
     }
+}
+
+/// Resolves the host name, IPv4 only since the announcement socket is bound to an IPv4 interface.
+fn resolve_unicast_target(target: &str) -> Result<Vec<SocketAddr>, String> {
+    let addresses: Vec<SocketAddr> = parse_unicast_target(target)?
+        .to_socket_addrs()
+        .map_err(|e| format!("Unable to resolve {}: {}", target, e))?
+        .filter(SocketAddr::is_ipv4)
+        .collect();
 
-    let udp_data = format!("{}:{}", UDP_PACKET_PREFIX, simconnect_port);
+    if addresses.is_empty() {
+        return Err(format!("{} has no IPv4 address", target));
+    }
+
+    Ok(addresses)
+}
 
+/// Resolves the unicast targets every `resolve_interval`, away from the broadcast cycle since lookups block.
+/// Targets without any address are retried with the next announcement, the thread ends with the broadcaster.
+fn unicast_resolve_thread(
+    targets: Arc<Mutex<Vec<UnicastTarget>>>,
+    resolve_interval: Duration,
+    stop: mpsc::Receiver<()>,
+) {
     loop {
-        match socket.send_to(udp_data.as_bytes(), UDP_BROADCAST_ADDRESS) {
+        let names: Vec<String> = match targets.lock() {
+            Ok(targets) => targets.iter().map(|target| target.target.clone()).collect(),
+            Err(_) => break,
+        };
+
+        for (index, name) in names.iter().enumerate() {
+            let result = resolve_unicast_target(name);
+
+            let Ok(mut targets) = targets.lock() else {
+                return;
+            };
+            let Some(target) = targets.get_mut(index) else {
+                continue;
+            };
+
+            match result {
+                Ok(addresses) => {
+                    target.addresses = addresses;
+                    target.resolve_error = None;
+                }
+                Err(error) => {
+                    /* Logged once per failure streak */
+                    if target.resolve_error.is_none() {
+                        log::warn!("Unicast target {}: {}", name, error);
+                    }
+                    target.resolve_error = Some(error);
+                }
+            }
+        }
+
+        let is_unresolved = targets
+            .lock()
+            .map(|targets| targets.iter().any(|target| target.addresses.is_empty()))
+            .unwrap_or(false);
+        let wait = if is_unresolved {
+            UDP_BROADCAST_INTERVAL
+        } else {
+            resolve_interval
+        };
+
+        match stop.recv_timeout(wait) {
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            _ => break,
+        }
+    }
+}
+
+/// Addresses of every unicast target, or why a target has none yet.
+fn unicast_addresses(targets: &Mutex<Vec<UnicastTarget>>) -> Vec<Result<Vec<SocketAddr>, String>> {
+    targets
+        .lock()
+        .map(|targets| {
+            targets
+                .iter()
+                .map(|target| match &target.resolve_error {
+                    Some(error) if target.addresses.is_empty() => Err(error.clone()),
+                    None if target.addresses.is_empty() => {
+                        Err(format!("{} is not resolved yet", target.target))
+                    }
+                    _ => Ok(target.addresses.clone()),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Sends the announcement to every unicast target, failures only affect the target's status.
+/// Returns whether any target got it.
+fn send_unicast(
+    socket: &UdpSocket,
+    udp_data: &str,
+    targets: &Mutex<Vec<UnicastTarget>>,
+    stats: &Mutex<BroadcastStats>,
+) -> bool {
+    let mut is_sent = false;
+
+    for (index, addresses) in unicast_addresses(targets).into_iter().enumerate() {
+        let result = addresses.clone().and_then(|addresses| {
+            addresses
+                .iter()
+                .map(|address| {
+                    socket
+                        .send_to(udp_data.as_bytes(), address)
+                        .map_err(|e| format!("Sending to {} failed: {}", address, e))
+                })
+                .collect::<Result<Vec<usize>, String>>()
+                .map(|_| ())
+        });
+
+        let Ok(mut stats) = stats.lock() else {
+            continue;
+        };
+        let Some(target_stats) = stats.unicast_targets.get_mut(index) else {
+            continue;
+        };
+
+        if let Ok(addresses) = addresses {
+            target_stats.addresses = addresses;
+        }
+
+        match result {
+            Ok(_) => {
+                is_sent = true;
+                target_stats.packets_sent += 1;
+                target_stats.last_sent_at = unix_now();
+                target_stats.last_error = None;
+            }
+            Err(error) => {
+                /* Logged once per failure streak, the broadcast cycle would repeat it every time */
+                if target_stats.last_error.is_none() {
+                    log::warn!("Unicast announcement to {}: {}", target_stats.target, error);
+                }
+
+                target_stats.send_errors += 1;
+                target_stats.last_error = Some(error);
+            }
+        }
+    }
+
+    is_sent
+}
+
+pub struct BroadcastConfig {
+    pub simconnect_port: String,
+    pub interface: ExposedInterface,
+    pub ipv6: Option<SimConnectEndpoint>,
+    /// Interfaces the IPv6 announcement is multicast on.
+    pub ipv6_scope_ids: Vec<u32>,
+    pub unicast: UnicastSettings,
+    /// Start without announcing, until `BroadcastCommand::Announce`.
+    pub is_withdrawn: bool,
+    pub stats: Arc<Mutex<BroadcastStats>>,
+}
+
+pub fn bind_socket(interface: &ExposedInterface) -> Option<UdpSocket> {
+    let socket = UdpSocket::bind(SocketAddrV4::new(interface.address, 0)).ok()?;
+    socket.set_broadcast(true).ok()?;
+    Some(socket)
+}
+
+fn ipv6_announcement(endpoint: &SimConnectEndpoint) -> Announcement {
+    Announcement {
+        family: AddressFamily::Ipv6,
+        port: endpoint.port.clone(),
+        address: endpoint
+            .address
+            .parse()
+            .ok()
+            .filter(|address: &IpAddr| !address.is_unspecified()),
+        via: Vec::new(),
+        is_withdrawal: false,
+    }
+}
+
+/// Best effort, receivers still time out the host if the withdrawal gets lost.
+fn send_withdrawal(
+    socket: &UdpSocket,
+    interface: &ExposedInterface,
+    withdrawal: &str,
+    unicast_targets: &Mutex<Vec<UnicastTarget>>,
+    ipv6: Option<(&UdpSocket, &str, &[u32])>,
+) {
+    let target = SocketAddrV4::new(interface.broadcast, UDP_BROADCAST_PORT);
+
+    if let Err(e) = socket.send_to(withdrawal.as_bytes(), target) {
+        log::warn!("Withdrawal to {} failed: {}", target, e);
+    }
+
+    for address in unicast_addresses(unicast_targets)
+        .into_iter()
+        .flatten()
+        .flatten()
+    {
+        socket.send_to(withdrawal.as_bytes(), address).ok();
+    }
+
+    if let Some((ipv6_socket, ipv6_withdrawal, scope_ids)) = ipv6 {
+        for scope_id in scope_ids {
+            let target =
+                SocketAddrV6::new(UDP_MULTICAST_V6_ADDRESS, UDP_BROADCAST_PORT, 0, *scope_id);
+            ipv6_socket.send_to(ipv6_withdrawal.as_bytes(), target).ok();
+        }
+    }
+
+    log::info!("Sent '{}'", withdrawal);
+}
+
+/// Names the interfaces the announcement is sent on, by address or by IPv6 scope.
+fn describe_interfaces(interface: &ExposedInterface, ipv6_scope_ids: &[u32]) -> Vec<String> {
+    let interfaces = list_interfaces().unwrap_or_default();
+
+    let ipv4 = if interface.address.is_unspecified() {
+        format!("All interfaces (→ {})", interface.broadcast)
+    } else {
+        let name = interfaces
+            .iter()
+            .find(|candidate| candidate.address == IpAddr::V4(interface.address))
+            .map(|candidate| candidate.name.as_str())
+            .unwrap_or("Interface");
+
+        format!("{} ({} → {})", name, interface.address, interface.broadcast)
+    };
+
+    let ipv6 = ipv6_scope_ids.iter().map(|scope_id| {
+        let name = interfaces
+            .iter()
+            .find(|candidate| candidate.index == Some(*scope_id))
+            .map(|candidate| candidate.name.clone())
+            .unwrap_or_else(|| format!("Scope {}", scope_id));
+
+        format!("{} (IPv6 multicast)", name)
+    });
+
+    std::iter::once(ipv4).chain(ipv6).collect()
+}
+
+/// Multicasts on every scope, whose stats follow the IPv4 broadcast's. Returns whether any scope got it.
+fn send_ipv6(
+    socket: &UdpSocket,
+    udp_data: &str,
+    scope_ids: &[u32],
+    stats: &Mutex<BroadcastStats>,
+) -> bool {
+    let mut is_sent = false;
+
+    for (index, scope_id) in scope_ids.iter().enumerate() {
+        let target = SocketAddrV6::new(UDP_MULTICAST_V6_ADDRESS, UDP_BROADCAST_PORT, 0, *scope_id);
+        let result = match socket.send_to(udp_data.as_bytes(), target) {
             Ok(_) => {
-                if !is_success_sent {
-                    sender.send(UDP_THREAD_STATUS_OK.to_string()).unwrap();
-                    is_success_sent = true;
+                metrics().record_packet_sent("ipv6", scope_id.to_string());
+                is_sent = true;
+                Ok(())
+            }
+            Err(e) => {
+                metrics().record_send_error("ipv6", scope_id.to_string());
+                Err(format!("Sending to {} failed: {}", target, e))
+            }
+        };
+
+        record_interface_result(stats, index + 1, result);
+    }
+
+    is_sent
+}
+
+pub fn udp_broadcast_thread(
+    proxy: EventLoopProxy<AppEvent>,
+    commands: mpsc::Receiver<BroadcastCommand>,
+    config: BroadcastConfig,
+) {
+    /* `None` until the first cycle, so the first outcome is always reported */
+    let mut is_announcing = None;
+    let interface = config.interface;
+
+    let socket = match bind_socket(&interface) {
+        Some(socket) => socket,
+        None => {
+            log::error!("Unable to open broadcast socket on {}", interface.address);
+            proxy.send_event(AppEvent::BroadcastError).ok();
+            return;
+        }
+    };
+
+    /* IPv6 is best effort, the IPv4 broadcast keeps working without it */
+    let ipv6_socket =
+        config
+            .ipv6
+            .as_ref()
+            .and_then(|_| match UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0)) {
+                Ok(socket) => Some(socket),
+                Err(e) => {
+                    log::warn!("IPv6 announcements disabled: {}", e);
+                    None
+                }
+            });
+    let ipv6_data = config
+        .ipv6
+        .as_ref()
+        .map(|endpoint| ipv6_announcement(endpoint).encode_versioned());
+    let ipv6_withdrawal = config
+        .ipv6
+        .as_ref()
+        .map(|endpoint| ipv6_announcement(endpoint).encode_withdrawal());
+    let multicast_scope_ids: &[u32] = match ipv6_socket {
+        Some(_) => &config.ipv6_scope_ids,
+        None => &[],
+    };
+
+    let announcement = Announcement {
+        family: AddressFamily::Ipv4,
+        port: config.simconnect_port.clone(),
+        address: None,
+        via: Vec::new(),
+        is_withdrawal: false,
+    };
+    let udp_data = announcement.encode_legacy();
+    let withdrawal = announcement.encode_withdrawal();
+    let mut is_withdrawn = config.is_withdrawn;
+
+    log::info!(
+        "Broadcasting '{}' to {} every {}s",
+        udp_data,
+        interface.broadcast,
+        UDP_BROADCAST_INTERVAL.as_secs()
+    );
+
+    let unicast_targets: Arc<Mutex<Vec<UnicastTarget>>> = Arc::new(Mutex::new(
+        config
+            .unicast
+            .targets
+            .iter()
+            .map(|target| UnicastTarget::new(target))
+            .collect(),
+    ));
+    /* Dropped with this thread, which stops the resolver */
+    let (_resolver_stop, resolver_stop_receiver) = mpsc::channel();
+
+    if !config.unicast.targets.is_empty() {
+        let unicast_targets = unicast_targets.clone();
+        let resolve_interval =
+            Duration::from_secs(config.unicast.resolve_interval_secs).max(UDP_BROADCAST_INTERVAL);
+
+        std::thread::spawn(move || {
+            unicast_resolve_thread(unicast_targets, resolve_interval, resolver_stop_receiver)
+        });
+    }
+
+    if let Ok(mut stats) = config.stats.lock() {
+        stats.interfaces = describe_interfaces(&interface, multicast_scope_ids)
+            .into_iter()
+            .map(InterfaceStats::new)
+            .collect();
+        stats.unicast_targets = config
+            .unicast
+            .targets
+            .iter()
+            .map(|target| UnicastTargetStats {
+                target: target.trim().to_string(),
+                addresses: Vec::new(),
+                packets_sent: 0,
+                send_errors: 0,
+                last_sent_at: None,
+                last_error: None,
+            })
+            .collect();
+    }
+
+    loop {
+        if !is_withdrawn {
+            let target = SocketAddrV4::new(interface.broadcast, UDP_BROADCAST_PORT);
+
+            /* A network blocking broadcast may still pass unicast or multicast, so every path is tried */
+            let broadcast_result = match socket.send_to(udp_data.as_bytes(), target) {
+                Ok(_) => {
+                    metrics().record_packet_sent("ipv4", interface.address.to_string());
+                    Ok(())
+                }
+                Err(e) => {
+                    if let Ok(mut stats) = config.stats.lock() {
+                        stats.send_errors += 1;
+                    }
+                    metrics().record_send_error("ipv4", interface.address.to_string());
+                    Err(format!("Broadcast to {} failed: {}", target, e))
+                }
+            };
+            let mut is_sent = broadcast_result.is_ok();
+            record_interface_result(&config.stats, 0, broadcast_result);
+
+            is_sent |= send_unicast(&socket, &udp_data, &unicast_targets, &config.stats);
+
+            if let (Some(ipv6_socket), Some(ipv6_data)) = (&ipv6_socket, &ipv6_data) {
+                is_sent |= send_ipv6(ipv6_socket, ipv6_data, multicast_scope_ids, &config.stats);
+            }
+
+            if is_sent {
+                if let Ok(mut stats) = config.stats.lock() {
+                    stats.record_sent();
+                }
+                proxy.send_event(AppEvent::AnnouncementSent).ok();
+            }
+
+            if is_announcing != Some(is_sent) {
+                if is_sent {
+                    proxy.send_event(AppEvent::BroadcastOk).ok();
+                } else {
+                    log::error!("The announcement didn't get out on any interface or target");
+                    proxy.send_event(AppEvent::AnnouncementFailed).ok();
+                }
+                is_announcing = Some(is_sent);
+            }
+        }
+
+        match commands.recv_timeout(UDP_BROADCAST_INTERVAL) {
+            Ok(BroadcastCommand::Withdraw(done)) => {
+                if !is_withdrawn {
+                    send_withdrawal(
+                        &socket,
+                        &interface,
+                        &withdrawal,
+                        &unicast_targets,
+                        ipv6_socket.as_ref().zip(ipv6_withdrawal.as_deref()).map(
+                            |(ipv6_socket, ipv6_withdrawal)| {
+                                (
+                                    ipv6_socket,
+                                    ipv6_withdrawal,
+                                    config.ipv6_scope_ids.as_slice(),
+                                )
+                            },
+                        ),
+                    );
+                    is_withdrawn = true;
+                }
+
+                if let Some(done) = done {
+                    done.send(()).ok();
                 }
             }
-            Err(_) => {
-                sender.send(UDP_THREAD_STATUS_ERROR.to_string()).unwrap();
-                break;
+            Ok(BroadcastCommand::Announce) => {
+                if is_withdrawn {
+                    log::info!("Announcing again");
+                    is_withdrawn = false;
+                }
             }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unicast_target_defaults_to_the_announcement_port() {
+        assert_eq!(
+            parse_unicast_target("sim-pc.local"),
+            Ok(("sim-pc.local", UDP_BROADCAST_PORT))
+        );
+        assert_eq!(
+            parse_unicast_target("192.168.1.20"),
+            Ok(("192.168.1.20", UDP_BROADCAST_PORT))
+        );
+    }
+
+    #[test]
+    fn unicast_target_with_port() {
+        assert_eq!(
+            parse_unicast_target("sim-pc.local:4321"),
+            Ok(("sim-pc.local", 4321))
+        );
+        assert_eq!(
+            parse_unicast_target("192.168.1.20:1234"),
+            Ok(("192.168.1.20", 1234))
+        );
+    }
+
+    #[test]
+    fn malformed_unicast_targets() {
+        for target in ["", ":1234", "host:", "host:port", "host:70000"] {
+            assert!(parse_unicast_target(target).is_err(), "{}", target);
+        }
+    }
+
+    #[test]
+    fn unicast_target_resolves_to_ipv4() {
+        assert_eq!(
+            resolve_unicast_target("127.0.0.1:4321"),
+            Ok(vec![SocketAddr::from(([127, 0, 0, 1], 4321))])
+        );
+    }
+
+    #[test]
+    fn last_good_addresses_are_kept() {
+        let mut target = UnicastTarget::new("sim-pc.local");
+        target.addresses = vec![SocketAddr::from(([192, 168, 1, 20], UDP_BROADCAST_PORT))];
+        target.resolve_error = Some("Unable to resolve sim-pc.local".to_string());
+        let targets = Mutex::new(vec![target, UnicastTarget::new("other.local")]);
+
+        let addresses = unicast_addresses(&targets);
+
+        assert_eq!(
+            addresses[0],
+            Ok(vec![SocketAddr::from((
+                [192, 168, 1, 20],
+                UDP_BROADCAST_PORT
+            ))])
+        );
+        assert!(addresses[1].is_err());
+    }
+
+    #[test]
+    fn interface_errors_are_recorded_until_a_send_succeeds() {
+        let stats = Mutex::new(BroadcastStats {
+            interfaces: vec![
+                InterfaceStats::new("Ethernet (192.168.1.10 → 192.168.1.255)".to_string()),
+                InterfaceStats::new("Ethernet (IPv6 multicast)".to_string()),
+            ],
+            ..Default::default()
+        });
+
+        record_interface_result(&stats, 0, Err("Broadcast blocked".to_string()));
+        record_interface_result(&stats, 0, Err("Broadcast blocked".to_string()));
+        record_interface_result(&stats, 1, Ok(()));
+        /* Unknown interfaces are ignored */
+        record_interface_result(&stats, 2, Ok(()));
+
+        let interfaces = stats.lock().unwrap().interfaces.clone();
+        assert_eq!(interfaces[0].send_errors, 2);
+        assert_eq!(interfaces[0].packets_sent, 0);
+        assert_eq!(
+            interfaces[0].last_error.as_deref(),
+            Some("Broadcast blocked")
+        );
+        assert_eq!(interfaces[1].packets_sent, 1);
+        assert!(interfaces[1].last_sent_at.is_some());
+
+        record_interface_result(&stats, 0, Ok(()));
 
-        thread::sleep(Duration::from_secs(10));
+        let interfaces = stats.lock().unwrap().interfaces.clone();
+        assert_eq!(interfaces[0].packets_sent, 1);
+        assert_eq!(interfaces[0].last_error, None);
     }
 }
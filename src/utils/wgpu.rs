@@ -1,31 +1,40 @@
-use glyphon::{FontSystem, SwashCache, TextAtlas, TextRenderer};
+use glyphon::{FontSystem, SwashCache, TextArea, TextAtlas, TextRenderer};
 use wgpu::{
-    CompositeAlphaMode, Device, MultisampleState, PresentMode, Queue, Surface,
+    Adapter, CompositeAlphaMode, Device, Instance, MultisampleState, PresentMode, Queue, Surface,
     SurfaceConfiguration, TextureFormat, TextureUsages,
 };
 use winit::window::Window;
 
-pub async fn configure_wgpu(
-    window: &Window,
-) -> (
-    Device,
-    Queue,
-    Surface<'_>,
-    FontSystem,
-    SwashCache,
-    TextAtlas,
-    TextRenderer,
-) {
-    let wgpu_instance = wgpu::Instance::default();
-    let viewport = wgpu_instance.create_surface(window).unwrap();
+use crate::ui::{
+    layout::Layout,
+    rects::{Rect, RectRenderer, BACKGROUND},
+};
+
+/// Draws the window through the GPU, or wgpu's software adapter.
+pub struct GpuRenderer<'w> {
+    device: Device,
+    queue: Queue,
+    viewport: Surface<'w>,
+    surface_config: SurfaceConfiguration,
+    text_atlas: TextAtlas,
+    text_renderer: TextRenderer,
+    rect_renderer: RectRenderer,
+}
+
+async fn request_device(
+    wgpu_instance: &Instance,
+    viewport: &Surface<'_>,
+    force_fallback_adapter: bool,
+) -> Result<(Adapter, Device, Queue), String> {
     let adapter = wgpu_instance
         .request_adapter(&wgpu::RequestAdapterOptions {
             // Request an adapter which can render to our surface
-            compatible_surface: Some(&viewport),
+            compatible_surface: Some(viewport),
+            force_fallback_adapter,
             ..Default::default()
         })
         .await
-        .expect("Failed to find an appropriate adapter");
+        .ok_or("No adapter can render to the window")?;
 
     let (device, queue) = adapter
         .request_device(
@@ -37,13 +46,53 @@ pub async fn configure_wgpu(
             None,
         )
         .await
-        .expect("Failed to create device");
+        .map_err(|e| {
+            format!(
+                "Error creating device on {}: {}",
+                adapter.get_info().name,
+                e
+            )
+        })?;
 
-    let size = window.inner_size();
+    Ok((adapter, device, queue))
+}
 
-    let swapchain_format = TextureFormat::Bgra8UnormSrgb;
+/// Takes the default adapter, or the fallback one when there is no usable GPU.
+pub async fn configure_wgpu(window: &Window) -> Result<GpuRenderer<'_>, String> {
+    let wgpu_instance = wgpu::Instance::default();
+    let viewport = wgpu_instance
+        .create_surface(window)
+        .map_err(|e| format!("Error creating surface: {}", e))?;
+
+    let (adapter, device, queue) = match request_device(&wgpu_instance, &viewport, false).await {
+        Ok(device) => device,
+        Err(error) => {
+            log::warn!("{}, trying the fallback adapter", error);
+            request_device(&wgpu_instance, &viewport, true).await?
+        }
+    };
+
+    let adapter_info = adapter.get_info();
+    log::info!(
+        "Rendering with {} ({:?}, {:?})",
+        adapter_info.name,
+        adapter_info.backend,
+        adapter_info.device_type
+    );
+
+    /* Colors are given in linear space, software adapters don't always offer the usual format */
+    let formats = viewport.get_capabilities(&adapter).formats;
+    let swapchain_format = formats
+        .iter()
+        .copied()
+        .find(|format| *format == TextureFormat::Bgra8UnormSrgb)
+        .or_else(|| formats.iter().copied().find(TextureFormat::is_srgb))
+        .or(formats.first().copied())
+        .ok_or("The surface supports no texture format")?;
+
+    let size = window.inner_size();
 
-    let mut config = SurfaceConfiguration {
+    let surface_config = SurfaceConfiguration {
         usage: TextureUsages::RENDER_ATTACHMENT,
         format: swapchain_format,
         width: size.width,
@@ -54,21 +103,108 @@ pub async fn configure_wgpu(
         desired_maximum_frame_latency: 2,
     };
 
-    viewport.configure(&device, &config);
+    if size.width > 0 && size.height > 0 {
+        viewport.configure(&device, &surface_config);
+    }
 
-    let mut font_system = FontSystem::new();
-    let mut swash_cache: SwashCache = SwashCache::new();
-    let mut text_atlas: TextAtlas = TextAtlas::new(&device, &queue, swapchain_format);
-    let mut text_renderer =
+    let mut text_atlas = TextAtlas::new(&device, &queue, swapchain_format);
+    let text_renderer =
         TextRenderer::new(&mut text_atlas, &device, MultisampleState::default(), None);
+    let rect_renderer = RectRenderer::new(&device, swapchain_format);
 
-    (
+    Ok(GpuRenderer {
         device,
         queue,
         viewport,
-        font_system,
-        swash_cache,
+        surface_config,
         text_atlas,
         text_renderer,
-    )
+        rect_renderer,
+    })
+}
+
+impl GpuRenderer<'_> {
+    pub fn draw(
+        &mut self,
+        font_system: &mut FontSystem,
+        swash_cache: &mut SwashCache,
+        layout: &Layout,
+        text_areas: Vec<TextArea>,
+        rects: &[Rect],
+    ) -> Result<(), String> {
+        if self.surface_config.width != layout.size.width
+            || self.surface_config.height != layout.size.height
+        {
+            self.surface_config.width = layout.size.width;
+            self.surface_config.height = layout.size.height;
+            self.viewport.configure(&self.device, &self.surface_config);
+        }
+
+        self.text_renderer
+            .prepare(
+                &self.device,
+                &self.queue,
+                font_system,
+                &mut self.text_atlas,
+                layout.resolution(),
+                text_areas,
+                swash_cache,
+            )
+            .map_err(|e| format!("Error preparing text: {}", e))?;
+
+        self.rect_renderer.prepare(
+            &self.device,
+            &self.queue,
+            layout.size.width,
+            layout.size.height,
+            rects,
+        );
+
+        let frame = match self.viewport.get_current_texture() {
+            Ok(frame) => frame,
+            Err(error) => {
+                /* A lost or outdated surface is back with the next frame */
+                self.viewport.configure(&self.device, &self.surface_config);
+                return Err(format!("Error acquiring frame: {}", error));
+            }
+        };
+
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        {
+            let [r, g, b, a] = BACKGROUND.map(f64::from);
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color { r, g, b, a }),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            self.rect_renderer.render(&mut rpass);
+            self.text_renderer
+                .render(&self.text_atlas, &mut rpass)
+                .map_err(|e| format!("Error rendering text: {}", e))?;
+        }
+
+        self.queue.submit(Some(encoder.finish()));
+        frame.present();
+
+        self.text_atlas.trim();
+
+        Ok(())
+    }
 }